@@ -67,10 +67,292 @@
 
 #define FillConfig_FILL_RATE_MIN 0.5
 
+/**
+ * 错误码，c接口
+ * 数值一经发布不再修改，新增错误码只能追加
+ */
+enum ErrorCode {
+  /**
+   * 成功
+   */
+  Success = 0,
+  /**
+   * 未分类的错误
+   */
+  Unknown = 1,
+  /**
+   * 图片解码失败
+   */
+  ImageDecodeFailed = 2,
+  /**
+   * 未找到外部黑框
+   */
+  NoFrameFound = 3,
+  /**
+   * 未能从外部黑框提取四边形
+   */
+  QuadNotFound = 4,
+  /**
+   * 辅助定位点数量异常
+   */
+  AssistMarkCountMismatch = 5,
+  /**
+   * 透视变换矩阵计算失败
+   */
+  HomographyFailed = 6,
+  /**
+   * Mark JSON无效
+   */
+  MarkJsonInvalid = 7,
+  /**
+   * 引擎未初始化
+   */
+  EngineNotInitialized = 8,
+  /**
+   * 多帧融合中该帧填涂率与其他帧差异过大
+   */
+  FrameOutlier = 9,
+  /**
+   * 推理选项JSON无效
+   */
+  OptionsJsonInvalid = 10,
+  /**
+   * 识别参数不合法
+   */
+  InvalidConfig = 11,
+  /**
+   * 图片与所有已注册模板都不匹配
+   */
+  TemplateNotMatched = 12,
+  /**
+   * 多页答题卡还有页面未扫描
+   */
+  PagesIncomplete = 13,
+  /**
+   * 标准答案与模板不对应
+   */
+  AnswerKeyInvalid = 14,
+  /**
+   * 采集标准答案时存在不确定的题目
+   */
+  AnswerKeyAmbiguous = 15,
+  /**
+   * 未找到四个角的定位块
+   */
+  CornerMarksNotFound = 16,
+  /**
+   * 无法判断答题卡方向
+   */
+  OrientationNotDetected = 17,
+  /**
+   * 外框贴近画面边缘，答题卡可能未拍全
+   */
+  BoundaryTouchesEdge = 18,
+  /**
+   * 外框面积过小
+   */
+  BoundaryTooSmall = 19,
+  /**
+   * 外框不是凸四边形或内角异常
+   */
+  BoundaryDistorted = 20,
+  /**
+   * 外框宽高比与模板不符
+   */
+  BoundaryAspectMismatch = 21,
+  /**
+   * 接口参数无效，如原始帧的尺寸、行跨度或数据长度不符
+   */
+  InvalidInput = 22,
+};
+typedef int32_t ErrorCode;
+
+/**
+ * 原始像素格式，c接口
+ */
+enum PixelFormat {
+  /**
+   * Android相机默认格式，Y平面后紧跟VU交错平面
+   */
+  Nv21 = 1,
+  /**
+   * I420格式，Y平面后依次为U、V平面
+   */
+  Yuv420 = 2,
+  /**
+   * iOS相机默认格式
+   */
+  Bgra = 3,
+  Rgba = 4,
+};
+typedef int32_t PixelFormat;
+
+/**
+ * 识别引擎
+ */
+typedef struct RecEngine RecEngine;
+
+/**
+ * 模板注册表：持有多个模板的引擎，识别时自动选择匹配的模板
+ */
+typedef struct TemplateRegistry TemplateRegistry;
+
+/**
+ * 多页识别会话：逐页扫描同一考生的答题卡，合并为一份结果
+ * 每张图片自动识别页码，重复扫描同一页时以最后一次为准
+ */
+typedef struct ScanSession ScanSession;
+
 char *initialize(const char *mark_ptr);
 
 char *inference(const uint8_t *data_ptr, uintptr_t data_len);
 
+/**
+ * 创建识别引擎句柄，返回初始化信息
+ * 成功时句柄写入engine_out，失败时写入空指针；句柄需通过destroy_engine释放
+ * 同一句柄可在多个线程中并发调用engine_inference
+ */
+char *create_engine(const char *mark_ptr, struct RecEngine **engine_out);
+
+/**
+ * 使用指定识别参数创建引擎句柄，config_ptr为RecConfig的JSON字符串
+ * 只覆盖其中写出的字段，其余字段使用Mark中的config字段或默认值
+ */
+char *create_engine_with_config(const char *mark_ptr,
+                                const char *config_ptr,
+                                struct RecEngine **engine_out);
+
+/**
+ * 使用指定引擎句柄识别编码后的图片数据
+ */
+char *engine_inference(const struct RecEngine *engine, const uint8_t *data_ptr, uintptr_t data_len);
+
+/**
+ * 使用指定引擎句柄识别相机原始帧
+ * data_len为缓冲区字节数，format取值见PixelFormat，stride为每行字节数，传0表示无行填充
+ */
+char *engine_inference_raw(const struct RecEngine *engine,
+                           const uint8_t *data_ptr,
+                           uintptr_t data_len,
+                           int32_t width,
+                           int32_t height,
+                           int32_t stride,
+                           int32_t format);
+
+/**
+ * 使用指定引擎句柄和推理选项识别编码后的图片数据
+ * options_ptr为InferenceOptions的JSON字符串，传空指针使用默认选项
+ */
+char *engine_inference_with_options(const struct RecEngine *engine,
+                                    const uint8_t *data_ptr,
+                                    uintptr_t data_len,
+                                    const char *options_ptr);
+
+/**
+ * 多帧融合识别同一张答题卡的多张编码图片
+ * data_ptrs和data_lens为长度为count的数组，分别对应每帧的数据指针和长度
+ */
+char *engine_inference_multi(const struct RecEngine *engine,
+                             const uint8_t *const *data_ptrs,
+                             const uintptr_t *data_lens,
+                             uintptr_t count);
+
+/**
+ * 扫描老师填涂的答案卡生成标准答案
+ * 成功时返回可直接用于Mark.answer_key或推理选项的标准答案，有不确定的题目时返回AnswerKeyAmbiguous和题目列表
+ */
+char *engine_capture_answer_key(const struct RecEngine *engine,
+                                const uint8_t *data_ptr,
+                                uintptr_t data_len);
+
+/**
+ * 预览检查编码后的图片，只做外框定位，用于实时拍摄引导
+ * check_assist为true时额外检查辅助定位点数量
+ */
+char *engine_preview(const struct RecEngine *engine,
+                     const uint8_t *data_ptr,
+                     uintptr_t data_len,
+                     bool check_assist);
+
+/**
+ * 预览检查相机原始帧，参数含义同engine_inference_raw
+ */
+char *engine_preview_raw(const struct RecEngine *engine,
+                         const uint8_t *data_ptr,
+                         uintptr_t data_len,
+                         int32_t width,
+                         int32_t height,
+                         int32_t stride,
+                         int32_t format,
+                         bool check_assist);
+
+/**
+ * 创建模板注册表句柄，templates_ptr为TemplateEntry数组的JSON字符串
+ * config_ptr为对所有模板生效的RecConfig，传空指针时使用各Mark中的config字段或默认值
+ * 句柄需通过destroy_registry释放
+ */
+char *create_registry(const char *templates_ptr,
+                      const char *config_ptr,
+                      struct TemplateRegistry **registry_out);
+
+/**
+ * 自动选择匹配的模板识别编码后的图片数据，结果中返回模板名称和匹配分数
+ * options_ptr为InferenceOptions的JSON字符串，传空指针使用默认选项
+ */
+char *registry_inference(const struct TemplateRegistry *registry,
+                         const uint8_t *data_ptr,
+                         uintptr_t data_len,
+                         const char *options_ptr);
+
+/**
+ * 释放模板注册表句柄
+ */
+void destroy_registry(struct TemplateRegistry *registry);
+
+/**
+ * 创建多页识别会话句柄，mark_ptr为MultiPageMark的JSON字符串
+ * config_ptr为对所有页面生效的RecConfig，传空指针时使用各页Mark中的config字段或默认值
+ * 句柄需通过destroy_session释放
+ */
+char *create_session(const char *mark_ptr,
+                     const char *config_ptr,
+                     struct ScanSession **session_out);
+
+/**
+ * 识别一页编码后的图片数据并记入会话，返回该页结果，页码自动识别
+ * options_ptr为InferenceOptions的JSON字符串，传空指针使用默认选项
+ */
+char *session_add_page(const struct ScanSession *session,
+                       const uint8_t *data_ptr,
+                       uintptr_t data_len,
+                       const char *options_ptr);
+
+/**
+ * 合并会话中已扫描的页面，题号跨页连续；有页面未扫描时code为PagesIncomplete
+ */
+char *session_result(const struct ScanSession *session);
+
+/**
+ * 清空会话中已扫描的页面，开始下一位考生
+ */
+void session_reset(const struct ScanSession *session);
+
+/**
+ * 释放多页识别会话句柄
+ */
+void destroy_session(struct ScanSession *session);
+
+/**
+ * 从空白答题卡的编码图片生成Mark草稿，不需要引擎句柄
+ * config_ptr为RecConfig的JSON字符串，传空指针使用默认值
+ */
+char *generate_mark(const uint8_t *data_ptr, uintptr_t data_len, const char *config_ptr);
+
+/**
+ * 释放引擎句柄
+ */
+void destroy_engine(struct RecEngine *engine);
+
 /**
  * 释放C字符串内存
  */
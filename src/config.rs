@@ -1,6 +1,104 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::{ErrorCode, WithCode};
+
+/// 识别参数，可在初始化时传入或写在Mark JSON的config字段中
+/// 未指定的字段使用各配置结构中的常量作为默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecConfig {
+    pub image_processing: ImageProcessingConfig,
+    pub assist_location: AssistLocationConfig,
+    pub fill: FillConfig,
+    pub crop: CropConfig,
+}
+
+impl RecConfig {
+    /// 校验所有参数，返回全部不合法的字段
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+        self.image_processing.validate(&mut errors);
+        self.assist_location.validate(&mut errors);
+        self.fill.validate(&mut errors);
+        self.crop.validate(&mut errors);
+        if !errors.is_empty() {
+            crate::bail_code!(ErrorCode::InvalidConfig, "识别参数校验失败: {}", errors.join("; "));
+        }
+        Ok(())
+    }
+}
+
+/// 初始化时传入的识别参数，只覆盖调用方在JSON中写出的字段
+/// 未写出的字段沿用Mark中的config字段或默认值
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOverride(Value);
+
+impl ConfigOverride {
+    /// 从RecConfig的JSON解析，字段类型错误时立即报错
+    pub fn from_json(input: &str) -> anyhow::Result<Self> {
+        let value: Value = serde_json::from_str(input).with_code(ErrorCode::InvalidConfig, "识别参数解析失败")?;
+        if !value.is_object() {
+            crate::bail_code!(ErrorCode::InvalidConfig, "识别参数必须是JSON对象");
+        }
+        serde_json::from_value::<RecConfig>(value.clone()).with_code(ErrorCode::InvalidConfig, "识别参数解析失败")?;
+        Ok(Self(value))
+    }
+
+    /// 将写出的字段逐个合并到base上
+    pub fn apply(&self, base: &RecConfig) -> anyhow::Result<RecConfig> {
+        let mut merged = serde_json::to_value(base).with_code(ErrorCode::InvalidConfig, "识别参数序列化失败")?;
+        merge_json(&mut merged, &self.0);
+        serde_json::from_value(merged).with_code(ErrorCode::InvalidConfig, "识别参数合并失败")
+    }
+}
+
+/// 完整的RecConfig覆盖全部字段
+impl From<RecConfig> for ConfigOverride {
+    fn from(config: RecConfig) -> Self {
+        Self(serde_json::to_value(config).unwrap_or(Value::Object(Default::default())))
+    }
+}
+
+/// 递归合并JSON对象，patch中的非对象值直接替换base中的同名字段
+fn merge_json(base: &mut Value, patch: &Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge_json(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, patch) => *base = patch.clone(),
+    }
+}
+
+/// 检查参数是否在闭区间内，不在时记录错误
+fn check_range<T: PartialOrd + std::fmt::Display>(errors: &mut Vec<String>, name: &str, value: T, min: T, max: T) {
+    if value < min || value > max {
+        errors.push(format!("{}取值{}超出范围[{}, {}]", name, value, min, max));
+    }
+}
+
+/// 检查参数是否为奇数
+fn check_odd(errors: &mut Vec<String>, name: &str, value: i32) {
+    if value % 2 == 0 {
+        errors.push(format!("{}必须为奇数，当前为{}", name, value));
+    }
+}
 
 /// 图像处理配置参数
-pub struct ImageProcessingConfig;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageProcessingConfig {
+    pub gaussian_kernel_size: i32,
+    pub gaussian_sigma: f64,
+    pub target_width: i32,
+    pub block_size: i32,
+    pub c: i32,
+    pub morph_kernel: i32,
+    pub epsilon_factor: f64,
+    pub min_area_ratio: f64,
+    pub margin_penalty: f64,
+}
 
 impl ImageProcessingConfig {
     /// 高斯模糊核大小
@@ -29,10 +127,52 @@ impl ImageProcessingConfig {
     
     /// 边界惩罚系数
     pub const MARGIN_PENALTY: f64 = 50.0;
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        check_range(errors, "gaussian_kernel_size", self.gaussian_kernel_size, 1, 31);
+        check_odd(errors, "gaussian_kernel_size", self.gaussian_kernel_size);
+        check_range(errors, "gaussian_sigma", self.gaussian_sigma, 0.0, 10.0);
+        check_range(errors, "target_width", self.target_width, 800, 8000);
+        check_range(errors, "block_size", self.block_size, 3, 255);
+        check_odd(errors, "block_size", self.block_size);
+        check_range(errors, "c", self.c, -50, 50);
+        check_range(errors, "morph_kernel", self.morph_kernel, 1, 31);
+        check_range(errors, "epsilon_factor", self.epsilon_factor, 0.001, 0.1);
+        check_range(errors, "min_area_ratio", self.min_area_ratio, 0.01, 1.0);
+        check_range(errors, "margin_penalty", self.margin_penalty, 0.0, 10000.0);
+    }
+}
+
+impl Default for ImageProcessingConfig {
+    fn default() -> Self {
+        Self {
+            gaussian_kernel_size: Self::GAUSSIAN_KERNEL_SIZE,
+            gaussian_sigma: Self::GAUSSIAN_SIGMA,
+            target_width: Self::TARGET_WIDTH,
+            block_size: Self::BLOCK_SIZE,
+            c: Self::C,
+            morph_kernel: Self::MORPH_KERNEL,
+            epsilon_factor: Self::EPSILON_FACTOR,
+            min_area_ratio: Self::MIN_AREA_RATIO,
+            margin_penalty: Self::MARGIN_PENALTY,
+        }
+    }
 }
 
 
-pub struct AssistLocationConfig;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssistLocationConfig {
+    pub assist_area_extend_size: i32,
+    pub assist_point_min_size: i32,
+    pub assist_point_max_size: i32,
+    pub assist_point_min_area: f64,
+    pub assist_point_max_area: f64,
+    pub assist_point_min_fill_ratio: f64,
+    pub assist_point_whdiff_max: i32,
+    pub piecewise_residual: f64,
+}
+
 impl AssistLocationConfig {
     pub const ASSIST_AREA_EXTEND_SIZE: i32 = 6;
     /// 辅助定位点的标准大小
@@ -42,10 +182,276 @@ impl AssistLocationConfig {
     pub const ASSIST_POINT_MAX_AREA: f64 = 70.0;
     pub const ASSIST_POINT_MIN_FILL_RATIO: f64 = 0.9;
     pub const ASSIST_POINT_WHDIFF_MAX: i32 = 2;
+    /// 辅助定位点经单应性变换后的均方根偏差（不含误检点）超过此像素数时，认为纸张弯曲，改用分段映射
+    pub const PIECEWISE_RESIDUAL: f64 = 2.0;
+    /// 计算辅助定位点单应性变换时RANSAC的重投影阈值（像素）
+    pub const RANSAC_THRESHOLD: f64 = 3.0;
+    /// 偏差超过所有辅助定位点偏差中位数的此倍数时视为误检
+    pub const OUTLIER_RESIDUAL_FACTOR: f64 = 3.0;
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        check_range(errors, "assist_area_extend_size", self.assist_area_extend_size, 0, 200);
+        check_range(errors, "assist_point_min_size", self.assist_point_min_size, 1, 200);
+        check_range(errors, "assist_point_max_size", self.assist_point_max_size, self.assist_point_min_size, 200);
+        check_range(errors, "assist_point_min_area", self.assist_point_min_area, 0.0, 40000.0);
+        check_range(errors, "assist_point_max_area", self.assist_point_max_area, self.assist_point_min_area, 40000.0);
+        check_range(errors, "assist_point_min_fill_ratio", self.assist_point_min_fill_ratio, 0.0, 1.0);
+        check_range(errors, "assist_point_whdiff_max", self.assist_point_whdiff_max, 0, 200);
+        check_range(errors, "piecewise_residual", self.piecewise_residual, 0.0, 1000.0);
+    }
+}
+
+impl Default for AssistLocationConfig {
+    fn default() -> Self {
+        Self {
+            assist_area_extend_size: Self::ASSIST_AREA_EXTEND_SIZE,
+            assist_point_min_size: Self::ASSIST_POINT_MIN_SIZE,
+            assist_point_max_size: Self::ASSIST_POINT_MAX_SIZE,
+            assist_point_min_area: Self::ASSIST_POINT_MIN_AREA,
+            assist_point_max_area: Self::ASSIST_POINT_MAX_AREA,
+            assist_point_min_fill_ratio: Self::ASSIST_POINT_MIN_FILL_RATIO,
+            assist_point_whdiff_max: Self::ASSIST_POINT_WHDIFF_MAX,
+            piecewise_residual: Self::PIECEWISE_RESIDUAL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FillConfig {
+    pub fill_rate_min: f64,
+    pub refine_coor_range: i32,
+    pub confidence_margin: f64,
+    pub borderline_margin: f64,
+    pub dominant_gap: f64,
+    pub erasure_aware: bool,
+    pub erased_min_gap: f64,
+    pub erased_min_contrast: f64,
 }
 
-pub struct FillConfig;
 impl FillConfig {
     pub const FILL_RATE_MIN: f64 = 0.45;
     pub const REFINE_COOR_RANGE: i32 = 2;
+    /// 填涂率与阈值的距离达到该值时置信度为1
+    pub const CONFIDENCE_MARGIN: f64 = 0.2;
+    /// 填涂率与阈值的距离小于该值时标记为临界
+    pub const BORDERLINE_MARGIN: f64 = 0.05;
+    /// 主选项策略下，最高填涂率领先第二名达到该值时视为明确作答
+    pub const DOMINANT_GAP: f64 = 0.15;
+    /// 是否结合灰度图深浅区分擦除痕迹
+    pub const ERASURE_AWARE: bool = false;
+    /// 填涂与擦除两类平均深浅的最小差距，差距不足时认为没有擦除
+    pub const ERASED_MIN_GAP: f64 = 0.15;
+    /// 擦除与空白两类平均深浅的最小差距，差距不足时擦除类只是空白选项的深浅波动
+    pub const ERASED_MIN_CONTRAST: f64 = 0.1;
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        check_range(errors, "fill_rate_min", self.fill_rate_min, 0.01, 1.0);
+        check_range(errors, "refine_coor_range", self.refine_coor_range, 0, 10);
+        check_range(errors, "confidence_margin", self.confidence_margin, 0.01, 1.0);
+        check_range(errors, "borderline_margin", self.borderline_margin, 0.0, 0.5);
+        check_range(errors, "dominant_gap", self.dominant_gap, 0.0, 1.0);
+        check_range(errors, "erased_min_gap", self.erased_min_gap, 0.0, 1.0);
+        check_range(errors, "erased_min_contrast", self.erased_min_contrast, 0.0, 1.0);
+    }
+}
+
+impl Default for FillConfig {
+    fn default() -> Self {
+        Self {
+            fill_rate_min: Self::FILL_RATE_MIN,
+            refine_coor_range: Self::REFINE_COOR_RANGE,
+            confidence_margin: Self::CONFIDENCE_MARGIN,
+            borderline_margin: Self::BORDERLINE_MARGIN,
+            dominant_gap: Self::DOMINANT_GAP,
+            erasure_aware: Self::ERASURE_AWARE,
+            erased_min_gap: Self::ERASED_MIN_GAP,
+            erased_min_contrast: Self::ERASED_MIN_CONTRAST,
+        }
+    }
+}
+
+/// 区域截图配置参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CropConfig {
+    pub format: String,
+    pub quality: i32,
+}
+
+impl CropConfig {
+    /// 截图编码格式
+    pub const FORMAT: &'static str = ".jpg";
+    /// 有损格式的编码质量，1-100，png格式忽略
+    pub const QUALITY: i32 = 90;
+    /// 支持的编码格式
+    pub const SUPPORTED_FORMATS: [&'static str; 4] = [".jpg", ".jpeg", ".png", ".webp"];
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if !Self::SUPPORTED_FORMATS.contains(&self.format.as_str()) {
+            errors.push(format!("format取值{}不支持，可选{:?}", self.format, Self::SUPPORTED_FORMATS));
+        }
+        check_range(errors, "quality", self.quality, 1, 100);
+    }
+}
+
+impl Default for CropConfig {
+    fn default() -> Self {
+        Self {
+            format: Self::FORMAT.to_string(),
+            quality: Self::QUALITY,
+        }
+    }
+}
+
+/// 多帧融合配置参数
+pub struct FusionConfig;
+impl FusionConfig {
+    /// 参与离群检测的最少帧数
+    pub const MIN_FRAMES_FOR_OUTLIER: usize = 3;
+    /// 单帧填涂率与中位数的平均偏差超过该值时剔除
+    pub const MAX_MEAN_DEVIATION: f64 = 0.15;
+}
+
+/// 条码识别配置参数
+pub struct CodeConfig;
+impl CodeConfig {
+    /// 截取条码区域时四周额外保留的像素，二维码检测需要空白边
+    pub const ROI_PADDING: i32 = 20;
+}
+
+/// 多模板识别配置参数
+pub struct TemplateConfig;
+impl TemplateConfig {
+    /// 最佳模板的匹配分数低于该值时拒绝识别
+    pub const MIN_MATCH_SCORE: f64 = 0.8;
+    /// 页码标识区域在二值图上的填涂率达到该值时视为存在
+    pub const PAGE_INDICATOR_MIN_FILL_RATE: f64 = 0.7;
+    /// 辅助定位点偏差（像素）达到该值时辅助定位点吻合度为0
+    pub const MAX_ASSIST_RESIDUAL: f64 = 40.0;
+    /// 选项区域平均墨迹比例达到该值时版式吻合度为1，印刷的选项框一般都能达到
+    pub const LAYOUT_FULL_INK: f64 = 0.15;
+    /// 最佳与次佳模板的匹配分数差距低于该值时无法确定模板
+    pub const MIN_SCORE_GAP: f64 = 0.05;
+}
+
+/// 标准答案采集配置参数
+pub struct KeyCaptureConfig;
+impl KeyCaptureConfig {
+    /// 题目置信度低于该值时视为不确定，比普通识别严格
+    pub const MIN_CONFIDENCE: f64 = 0.6;
+    /// 未指定分值时每题的默认分值
+    pub const DEFAULT_POINTS: f64 = 1.0;
+}
+
+/// 模板生成配置参数，尺寸均为模板空间中的像素
+pub struct MarkGenConfig;
+impl MarkGenConfig {
+    /// 模板空间中整页的宽度
+    pub const TEMPLATE_WIDTH: i32 = 600;
+    /// 外框内左右两侧寻找辅助定位点的条带宽度（占外框宽度比例）
+    pub const ASSIST_STRIP_RATIO: f64 = 0.06;
+    /// 寻找选项框时避开外框边线的距离
+    pub const FRAME_INSET: i32 = 3;
+    /// 选项框的最小、最大边长
+    pub const BUBBLE_MIN_SIZE: i32 = 5;
+    pub const BUBBLE_MAX_SIZE: i32 = 30;
+    /// 选项框宽高比范围，覆盖圆形和方括号形
+    pub const BUBBLE_MIN_ASPECT: f64 = 0.8;
+    pub const BUBBLE_MAX_ASPECT: f64 = 3.0;
+    /// 选项框内部最大填涂率，超过时视为实心块
+    pub const BUBBLE_MAX_FILL_RATIO: f64 = 0.6;
+    /// 与选项框尺寸中位数的最大相对偏差
+    pub const BUBBLE_SIZE_TOLERANCE: f64 = 0.3;
+    /// 同一题相邻选项的最大间距（选项框宽度的倍数）
+    pub const ITEM_GAP_FACTOR: f64 = 1.0;
+    /// 数字网格的最大行间距（选项框高度的倍数）
+    pub const GRID_MAX_PITCH_FACTOR: f64 = 2.0;
+    /// 数字网格各行间距与中位数的最大相对偏差
+    pub const GRID_PITCH_TOLERANCE: f64 = 0.2;
+}
+
+/// 外框亚像素精修配置参数
+pub struct BoundaryRefineConfig;
+impl BoundaryRefineConfig {
+    /// 距离边的直线不超过此像素数的轮廓点参与拟合
+    pub const EDGE_BAND: f64 = 4.0;
+    /// 每条边两端去掉的比例，避开圆角和顶点附近的噪声
+    pub const EDGE_TRIM_RATIO: f64 = 0.1;
+    /// 每条边参与拟合的最少点数
+    pub const MIN_EDGE_POINTS: usize = 10;
+    /// 精修后顶点允许移动的最大像素数，超过时保留原顶点
+    pub const MAX_CORNER_SHIFT: f64 = 8.0;
+    /// corner_sub_pix的半窗口大小
+    pub const SUB_PIX_WINDOW: i32 = 5;
+    pub const SUB_PIX_MAX_ITER: i32 = 30;
+    pub const SUB_PIX_EPSILON: f64 = 0.01;
+}
+
+/// 外框几何校验配置参数
+pub struct BoundaryValidationConfig;
+impl BoundaryValidationConfig {
+    /// 顶点到画面边缘的最小距离（占画面宽度比例）
+    pub const MIN_BORDER_MARGIN: f64 = 0.02;
+    /// 内角范围（度），超出说明拍摄角度过斜或定位错误
+    pub const MIN_INTERIOR_ANGLE: f64 = 60.0;
+    pub const MAX_INTERIOR_ANGLE: f64 = 120.0;
+    /// 与模板宽高比的最低吻合度，0-1
+    pub const MIN_ASPECT_SCORE: f64 = 0.75;
+}
+
+/// 方向检测配置参数
+pub struct OrientationConfig;
+impl OrientationConfig {
+    /// 方向标记处的最小填涂率，低于此值说明该方向不正确
+    pub const MARK_MIN_FILL_RATE: f64 = 0.7;
+    /// 多个方向都能对齐时，最佳方向的分数至少高出其他方向的差距
+    pub const MIN_SCORE_GAP: f64 = 0.02;
+}
+
+/// 四角定位块配置参数
+pub struct CornerLocatorConfig;
+impl CornerLocatorConfig {
+    /// 定位块面积占画面比例的范围
+    pub const MIN_AREA_RATIO: f64 = 0.00005;
+    pub const MAX_AREA_RATIO: f64 = 0.01;
+    /// 定位块宽高比与1的最大偏差
+    pub const MAX_ASPECT_DEVIATION: f64 = 0.3;
+    /// 轮廓面积占外接矩形面积的最小比例，用于排除空心框和文字
+    pub const MIN_SOLIDITY: f64 = 0.8;
+    /// 定位块中心到对应画面角落的最大距离（占画面对角线比例）
+    pub const MAX_CORNER_DISTANCE: f64 = 0.45;
+}
+
+/// 预览检查配置参数
+pub struct PreviewConfig;
+impl PreviewConfig {
+    /// 四边形面积占画面比例达到该值时视为距离合适
+    pub const FULL_AREA_RATIO: f64 = 0.5;
+    /// 四边形顶点到画面边缘的最小距离（占画面宽度比例）
+    pub const MIN_BORDER_MARGIN: f64 = 0.01;
+    /// 辅助定位点检查失败时的就绪分数惩罚系数
+    pub const ASSIST_FAIL_PENALTY: f64 = 0.5;
+    /// 就绪分数达到该值时可以自动拍摄
+    pub const READY_SCORE: f64 = 0.8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_override() -> anyhow::Result<()> {
+        let mark_config: RecConfig = serde_json::from_str(r#"{"fill": {"fill_rate_min": 0.6, "dominant_gap": 0.3}}"#)?;
+
+        // 只覆盖写出的字段，Mark中的其余设置保留
+        let config = ConfigOverride::from_json(r#"{"fill": {"dominant_gap": 0.2}}"#)?.apply(&mark_config)?;
+        assert_eq!(config.fill.fill_rate_min, 0.6);
+        assert_eq!(config.fill.dominant_gap, 0.2);
+        assert_eq!(config.image_processing.block_size, ImageProcessingConfig::BLOCK_SIZE);
+
+        let err = ConfigOverride::from_json(r#"{"fill": {"dominant_gap": "0.2"}}"#).unwrap_err();
+        assert_eq!(crate::error::error_code(&err), ErrorCode::InvalidConfig);
+        Ok(())
+    }
 }
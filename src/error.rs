@@ -0,0 +1,146 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// 错误码，c接口
+/// 数值一经发布不再修改，新增错误码只能追加
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "i32", try_from = "i32")]
+pub enum ErrorCode {
+    /// 成功
+    Success = 0,
+    /// 未分类的错误
+    Unknown = 1,
+    /// 图片解码失败
+    ImageDecodeFailed = 2,
+    /// 未找到外部黑框
+    NoFrameFound = 3,
+    /// 未能从外部黑框提取四边形
+    QuadNotFound = 4,
+    /// 辅助定位点数量异常
+    AssistMarkCountMismatch = 5,
+    /// 透视变换矩阵计算失败
+    HomographyFailed = 6,
+    /// Mark JSON无效
+    MarkJsonInvalid = 7,
+    /// 引擎未初始化
+    EngineNotInitialized = 8,
+    /// 多帧融合中该帧填涂率与其他帧差异过大
+    FrameOutlier = 9,
+    /// 推理选项JSON无效
+    OptionsJsonInvalid = 10,
+    /// 识别参数不合法
+    InvalidConfig = 11,
+    /// 图片与所有已注册模板都不匹配
+    TemplateNotMatched = 12,
+    /// 多页答题卡还有页面未扫描
+    PagesIncomplete = 13,
+    /// 标准答案与模板不对应
+    AnswerKeyInvalid = 14,
+    /// 采集标准答案时存在不确定的题目
+    AnswerKeyAmbiguous = 15,
+    /// 未找到四个角的定位块
+    CornerMarksNotFound = 16,
+    /// 无法判断答题卡方向
+    OrientationNotDetected = 17,
+    /// 外框贴近画面边缘，答题卡可能未拍全
+    BoundaryTouchesEdge = 18,
+    /// 外框面积过小
+    BoundaryTooSmall = 19,
+    /// 外框不是凸四边形或内角异常
+    BoundaryDistorted = 20,
+    /// 外框宽高比与模板不符
+    BoundaryAspectMismatch = 21,
+    /// 接口参数无效，如原始帧的尺寸、行跨度或数据长度不符
+    InvalidInput = 22,
+}
+
+impl From<ErrorCode> for i32 {
+    fn from(code: ErrorCode) -> Self {
+        code as i32
+    }
+}
+
+impl TryFrom<i32> for ErrorCode {
+    type Error = String;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ErrorCode::Success),
+            1 => Ok(ErrorCode::Unknown),
+            2 => Ok(ErrorCode::ImageDecodeFailed),
+            3 => Ok(ErrorCode::NoFrameFound),
+            4 => Ok(ErrorCode::QuadNotFound),
+            5 => Ok(ErrorCode::AssistMarkCountMismatch),
+            6 => Ok(ErrorCode::HomographyFailed),
+            7 => Ok(ErrorCode::MarkJsonInvalid),
+            8 => Ok(ErrorCode::EngineNotInitialized),
+            9 => Ok(ErrorCode::FrameOutlier),
+            10 => Ok(ErrorCode::OptionsJsonInvalid),
+            11 => Ok(ErrorCode::InvalidConfig),
+            12 => Ok(ErrorCode::TemplateNotMatched),
+            13 => Ok(ErrorCode::PagesIncomplete),
+            14 => Ok(ErrorCode::AnswerKeyInvalid),
+            15 => Ok(ErrorCode::AnswerKeyAmbiguous),
+            16 => Ok(ErrorCode::CornerMarksNotFound),
+            17 => Ok(ErrorCode::OrientationNotDetected),
+            18 => Ok(ErrorCode::BoundaryTouchesEdge),
+            19 => Ok(ErrorCode::BoundaryTooSmall),
+            20 => Ok(ErrorCode::BoundaryDistorted),
+            21 => Ok(ErrorCode::BoundaryAspectMismatch),
+            22 => Ok(ErrorCode::InvalidInput),
+            _ => Err(format!("未知错误码: {}", value)),
+        }
+    }
+}
+
+/// 携带错误码的识别错误
+#[derive(Debug, Clone)]
+pub struct RecError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl RecError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RecError {}
+
+/// 从错误链中提取错误码，没有携带错误码时返回Unknown
+pub fn error_code(err: &anyhow::Error) -> ErrorCode {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<RecError>())
+        .map(|e| e.code)
+        .unwrap_or(ErrorCode::Unknown)
+}
+
+/// 为Result附加错误码，用法类似anyhow::Context
+pub trait WithCode<T> {
+    fn with_code(self, code: ErrorCode, message: &str) -> anyhow::Result<T>;
+}
+
+impl<T, E: fmt::Display> WithCode<T> for Result<T, E> {
+    fn with_code(self, code: ErrorCode, message: &str) -> anyhow::Result<T> {
+        self.map_err(|e| RecError::new(code, format!("{}: {}", message, e)).into())
+    }
+}
+
+/// 携带错误码提前返回，用法类似anyhow::bail
+#[macro_export]
+macro_rules! bail_code {
+    ($code:expr, $($arg:tt)*) => {
+        return Err($crate::error::RecError::new($code, format!($($arg)*)).into())
+    };
+}
@@ -1,3 +1,4 @@
+pub mod error;
 pub mod myutils;
 pub mod models;
 pub mod recognize;
@@ -8,6 +9,7 @@ mod tests {
     use std::fs;
     use opencv::imgcodecs::imread;
     use crate::myutils::myjson::to_json;
+    use crate::models::{DebugOptions, InferenceOptions};
     use crate::recognize::engine;
     use anyhow::Result;
 
@@ -23,21 +25,143 @@ mod tests {
         let scan_string = fs::read_to_string(scan_path)?;
 
         let engine = engine::RecEngine::new(&scan_string)?;
-        let res = engine.inference(&image)?;
+        let options = InferenceOptions {
+            debug: Some(DebugOptions {
+                return_images: false,
+                output_dir: Some("dev/test_data/debug".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let res = engine.inference_with_options(&image, &options)?;
 
         fs::write(format!("dev/test_data/out/{scan_id}.json"), to_json(&res)?)?;
 
         Ok(())
     }
 
+    #[test]
+    fn test_config_validate() -> Result<()> {
+        let config: config::RecConfig = myutils::myjson::from_json(r#"{"fill": {"fill_rate_min": 0.5}}"#)?;
+        assert_eq!(config.fill.fill_rate_min, 0.5);
+        assert_eq!(config.image_processing.block_size, config::ImageProcessingConfig::BLOCK_SIZE);
+        config.validate()?;
+
+        let config: config::RecConfig = myutils::myjson::from_json(r#"{"image_processing": {"block_size": 50}}"#)?;
+        let err = config.validate().unwrap_err();
+        assert_eq!(error::error_code(&err), error::ErrorCode::InvalidConfig);
+
+        Ok(())
+    }
+
 }
 
 
 pub mod build {
-    use std::ffi::{c_char, CString};
-    use crate::{models::{InitInfo, MobileOutput}, myutils::myjson::{c_to_mat, c_to_string, to_json}, recognize::engine::RecEngine};
-    static mut ENGINE: Option<RecEngine> = None;
-    
+    use std::ffi::c_char;
+    use std::sync::RwLock;
+    use anyhow::Result;
+    use opencv::core::Mat;
+    use crate::{config::{ConfigOverride, RecConfig}, error::{error_code, ErrorCode, WithCode}, models::{InferenceOptions, InitInfo, KeyCaptureOutput, MarkDraftOutput, MobileOutput, PreviewOutput}, myutils::myjson::{c_raw_to_mat, c_to_mat, c_to_mats, c_to_string, from_json, to_c_json}, recognize::{engine::RecEngine, generate::MarkGenerator, registry::TemplateRegistry, session::ScanSession}};
+
+    /// 全局引擎，供initialize/inference旧接口使用
+    static ENGINE: RwLock<Option<RecEngine>> = RwLock::new(None);
+
+    /// 使用引擎识别转换后的图片
+    fn run_inference(engine: &RecEngine, image: Result<Mat>) -> MobileOutput {
+        run_inference_with_options(engine, image, &InferenceOptions::default())
+    }
+
+    /// 使用引擎和推理选项识别转换后的图片
+    fn run_inference_with_options(engine: &RecEngine, image: Result<Mat>, options: &InferenceOptions) -> MobileOutput {
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => return MobileOutput::from_error(&e),
+        };
+
+        match engine.inference_with_options(&image, options) {
+            Ok(output) => output,
+            Err(e) => {
+                let mut output = MobileOutput::from_error(&e);
+                if let Some(debug) = &options.debug {
+                    engine.attach_failure_debug_images(&image, debug, &mut output);
+                }
+                output
+            }
+        }
+    }
+
+    /// 使用模板注册表和推理选项识别转换后的图片
+    fn run_registry_inference(registry: &TemplateRegistry, image: Result<Mat>, options: &InferenceOptions) -> MobileOutput {
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => return MobileOutput::from_error(&e),
+        };
+
+        match registry.inference_with_options(&image, options) {
+            Ok(output) => output,
+            Err(e) => MobileOutput::from_error(&e),
+        }
+    }
+
+    /// 识别转换后的图片并记入多页会话
+    fn run_session_page(session: &ScanSession, image: Result<Mat>, options: &InferenceOptions) -> MobileOutput {
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => return MobileOutput::from_error(&e),
+        };
+
+        match session.add_page(&image, options) {
+            Ok(output) => output,
+            Err(e) => MobileOutput::from_error(&e),
+        }
+    }
+
+    /// 使用引擎对转换后的图片做预览检查
+    fn run_preview(engine: &RecEngine, image: Result<Mat>, check_assist: bool) -> PreviewOutput {
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => return PreviewOutput::from_error(&e),
+        };
+
+        match engine.preview(&image, check_assist) {
+            Ok(output) => output,
+            Err(e) => PreviewOutput::from_error(&e),
+        }
+    }
+
+    /// 解析推理选项，空指针时使用默认选项
+    fn c_to_options(options_ptr: *const c_char) -> Result<InferenceOptions> {
+        if options_ptr.is_null() {
+            return Ok(InferenceOptions::default());
+        }
+        from_json(&c_to_string(options_ptr)).with_code(ErrorCode::OptionsJsonInvalid, "推理选项解析失败")
+    }
+
+    /// 解析识别参数，只保留JSON中写出的字段，空指针时返回None
+    fn c_to_config(config_ptr: *const c_char) -> Result<Option<ConfigOverride>> {
+        if config_ptr.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(ConfigOverride::from_json(&c_to_string(config_ptr))?))
+    }
+
+    /// 将句柄转换为引用
+    fn handle_to_ref<'a, T>(handle: *const T) -> Option<&'a T> {
+        unsafe { handle.as_ref() }
+    }
+
+    /// 将句柄写入调用方提供的指针，指针为空时返回false
+    fn write_handle<T>(out: *mut *mut T, handle: *mut T) -> bool {
+        if out.is_null() {
+            return false;
+        }
+        unsafe {
+            *out = handle;
+        }
+        true
+    }
+
     #[no_mangle]
     pub extern "C" fn initialize(mark_ptr: *const c_char) -> *mut c_char{
         let mark_str = c_to_string(mark_ptr);
@@ -49,50 +173,325 @@ pub mod build {
             message: "初始化成功".to_string(),
         };
         
-        if engine.is_err() {
-            res.code = 1;
-            res.message = engine.err().unwrap().to_string();
-            return CString::new(to_json(&res).unwrap()).unwrap().into_raw()
+        match engine {
+            Ok(engine) => {
+                // 初始化引擎
+                *ENGINE.write().unwrap() = Some(engine);
+            }
+            Err(e) => {
+                res.code = error_code(&e).into();
+                res.message = e.to_string();
+            }
         }
 
-        // 初始化引擎
-        unsafe {
-            ENGINE = Some(engine.unwrap());
+        to_c_json(&res)
+    }
+
+
+    #[no_mangle]
+    pub extern "C" fn inference(data_ptr: *const u8, data_len: usize) -> *mut c_char {
+        let guard = ENGINE.read().unwrap();
+        let output = match guard.as_ref() {
+            Some(engine) => run_inference(engine, c_to_mat(data_ptr, data_len)),
+            None => MobileOutput::failed(ErrorCode::EngineNotInitialized, "请先初始化引擎".to_string()),
+        };
+        to_c_json(&output)
+    }
+
+    /// 创建识别引擎句柄，返回初始化信息
+    /// 成功时句柄写入engine_out，失败时写入空指针；句柄需通过destroy_engine释放
+    /// 同一句柄可在多个线程中并发调用engine_inference
+    #[no_mangle]
+    pub extern "C" fn create_engine(mark_ptr: *const c_char, engine_out: *mut *mut RecEngine) -> *mut c_char {
+        let mark_str = c_to_string(mark_ptr);
+        export_handle(RecEngine::new(&mark_str), engine_out)
+    }
+
+    /// 使用指定识别参数创建引擎句柄，config_ptr为RecConfig的JSON字符串
+    /// 只覆盖其中写出的字段，其余字段使用Mark中的config字段或默认值
+    #[no_mangle]
+    pub extern "C" fn create_engine_with_config(
+        mark_ptr: *const c_char,
+        config_ptr: *const c_char,
+        engine_out: *mut *mut RecEngine,
+    ) -> *mut c_char {
+        let mark_str = c_to_string(mark_ptr);
+        let engine = c_to_config(config_ptr)
+            .and_then(|config| RecEngine::with_config(&mark_str, config));
+        export_handle(engine, engine_out)
+    }
+
+    /// 将创建结果写入句柄，返回初始化信息
+    fn export_handle<T>(value: Result<T>, handle_out: *mut *mut T) -> *mut c_char {
+        let mut res = InitInfo {
+            code: 0,
+            message: "初始化成功".to_string(),
+        };
+
+        let handle = match value {
+            Ok(value) => Box::into_raw(Box::new(value)),
+            Err(e) => {
+                res.code = error_code(&e).into();
+                res.message = e.to_string();
+                std::ptr::null_mut()
+            }
+        };
+
+        if !write_handle(handle_out, handle) && !handle.is_null() {
+            // 调用方无法接收句柄，直接释放避免泄漏
+            unsafe {
+                drop(Box::from_raw(handle));
+            }
         }
 
-        return CString::new(to_json(&res).unwrap()).unwrap().into_raw()
+        to_c_json(&res)
     }
 
+    /// 使用指定引擎句柄识别编码后的图片数据
+    #[no_mangle]
+    pub extern "C" fn engine_inference(engine: *const RecEngine, data_ptr: *const u8, data_len: usize) -> *mut c_char {
+        let output = match handle_to_ref(engine) {
+            Some(engine) => run_inference(engine, c_to_mat(data_ptr, data_len)),
+            None => MobileOutput::failed(ErrorCode::EngineNotInitialized, "引擎句柄为空".to_string()),
+        };
+        to_c_json(&output)
+    }
 
+    /// 使用指定引擎句柄识别相机原始帧
+    /// data_len为缓冲区字节数，format取值见PixelFormat，stride为每行字节数，传0表示无行填充
     #[no_mangle]
-    pub extern "C" fn inference(data_ptr: *const u8, data_len: usize) -> *mut c_char {
-        let mut failed_output = MobileOutput {
-            code: 1,
-            message: "failed".to_string(),
-            rec_results: vec![],
+    pub extern "C" fn engine_inference_raw(
+        engine: *const RecEngine,
+        data_ptr: *const u8,
+        data_len: usize,
+        width: i32,
+        height: i32,
+        stride: i32,
+        format: i32,
+    ) -> *mut c_char {
+        let output = match handle_to_ref(engine) {
+            Some(engine) => run_inference(engine, c_raw_to_mat(data_ptr, data_len, width, height, stride, format)),
+            None => MobileOutput::failed(ErrorCode::EngineNotInitialized, "引擎句柄为空".to_string()),
         };
+        to_c_json(&output)
+    }
 
-        unsafe {
-            if ENGINE.is_none() {
-                failed_output.message = "请先初始化引擎".to_string();
-                return CString::new(to_json(&failed_output).unwrap()).unwrap().into_raw();
+    /// 使用指定引擎句柄和推理选项识别编码后的图片数据
+    /// options_ptr为InferenceOptions的JSON字符串，传空指针使用默认选项
+    #[no_mangle]
+    pub extern "C" fn engine_inference_with_options(
+        engine: *const RecEngine,
+        data_ptr: *const u8,
+        data_len: usize,
+        options_ptr: *const c_char,
+    ) -> *mut c_char {
+        let output = match (handle_to_ref(engine), c_to_options(options_ptr)) {
+            (None, _) => MobileOutput::failed(ErrorCode::EngineNotInitialized, "引擎句柄为空".to_string()),
+            (Some(_), Err(e)) => MobileOutput::from_error(&e),
+            (Some(engine), Ok(options)) => run_inference_with_options(engine, c_to_mat(data_ptr, data_len), &options),
+        };
+        to_c_json(&output)
+    }
+
+    /// 多帧融合识别同一张答题卡的多张编码图片
+    /// data_ptrs和data_lens为长度为count的数组，分别对应每帧的数据指针和长度
+    #[no_mangle]
+    pub extern "C" fn engine_inference_multi(
+        engine: *const RecEngine,
+        data_ptrs: *const *const u8,
+        data_lens: *const usize,
+        count: usize,
+    ) -> *mut c_char {
+        let output = match handle_to_ref(engine) {
+            Some(engine) => {
+                let images = c_to_mats(data_ptrs, data_lens, count);
+                match engine.inference_multi(&images) {
+                    Ok(output) => output,
+                    Err(e) => MobileOutput::from_error(&e),
+                }
+            }
+            None => MobileOutput::failed(ErrorCode::EngineNotInitialized, "引擎句柄为空".to_string()),
+        };
+        to_c_json(&output)
+    }
+
+    /// 扫描老师填涂的答案卡生成标准答案
+    /// 成功时返回可直接用于Mark.answer_key或推理选项的标准答案，有不确定的题目时返回AnswerKeyAmbiguous和题目列表
+    #[no_mangle]
+    pub extern "C" fn engine_capture_answer_key(engine: *const RecEngine, data_ptr: *const u8, data_len: usize) -> *mut c_char {
+        let output = match handle_to_ref(engine) {
+            Some(engine) => match c_to_mat(data_ptr, data_len).and_then(|image| engine.capture_answer_key(&image)) {
+                Ok(output) => output,
+                Err(e) => KeyCaptureOutput::from_error(&e),
+            },
+            None => KeyCaptureOutput::failed(ErrorCode::EngineNotInitialized, "引擎句柄为空".to_string()),
+        };
+        to_c_json(&output)
+    }
+
+    /// 预览检查编码后的图片，只做外框定位，用于实时拍摄引导
+    /// check_assist为true时额外检查辅助定位点数量
+    #[no_mangle]
+    pub extern "C" fn engine_preview(engine: *const RecEngine, data_ptr: *const u8, data_len: usize, check_assist: bool) -> *mut c_char {
+        let output = match handle_to_ref(engine) {
+            Some(engine) => run_preview(engine, c_to_mat(data_ptr, data_len), check_assist),
+            None => PreviewOutput::failed(ErrorCode::EngineNotInitialized, "引擎句柄为空".to_string()),
+        };
+        to_c_json(&output)
+    }
+
+    /// 预览检查相机原始帧，参数含义同engine_inference_raw
+    #[no_mangle]
+    pub extern "C" fn engine_preview_raw(
+        engine: *const RecEngine,
+        data_ptr: *const u8,
+        data_len: usize,
+        width: i32,
+        height: i32,
+        stride: i32,
+        format: i32,
+        check_assist: bool,
+    ) -> *mut c_char {
+        let output = match handle_to_ref(engine) {
+            Some(engine) => run_preview(engine, c_raw_to_mat(data_ptr, data_len, width, height, stride, format), check_assist),
+            None => PreviewOutput::failed(ErrorCode::EngineNotInitialized, "引擎句柄为空".to_string()),
+        };
+        to_c_json(&output)
+    }
+
+    /// 创建模板注册表句柄，templates_ptr为TemplateEntry数组的JSON字符串
+    /// config_ptr为对所有模板生效的RecConfig，传空指针时使用各Mark中的config字段或默认值
+    /// 句柄需通过destroy_registry释放
+    #[no_mangle]
+    pub extern "C" fn create_registry(
+        templates_ptr: *const c_char,
+        config_ptr: *const c_char,
+        registry_out: *mut *mut TemplateRegistry,
+    ) -> *mut c_char {
+        let templates_str = c_to_string(templates_ptr);
+        let registry = c_to_config(config_ptr)
+            .and_then(|config| TemplateRegistry::new(&templates_str, config));
+        export_handle(registry, registry_out)
+    }
+
+    /// 自动选择匹配的模板识别编码后的图片数据，结果中返回模板名称和匹配分数
+    /// options_ptr为InferenceOptions的JSON字符串，传空指针使用默认选项
+    #[no_mangle]
+    pub extern "C" fn registry_inference(
+        registry: *const TemplateRegistry,
+        data_ptr: *const u8,
+        data_len: usize,
+        options_ptr: *const c_char,
+    ) -> *mut c_char {
+        let output = match (handle_to_ref(registry), c_to_options(options_ptr)) {
+            (None, _) => MobileOutput::failed(ErrorCode::EngineNotInitialized, "模板注册表句柄为空".to_string()),
+            (Some(_), Err(e)) => MobileOutput::from_error(&e),
+            (Some(registry), Ok(options)) => run_registry_inference(registry, c_to_mat(data_ptr, data_len), &options),
+        };
+        to_c_json(&output)
+    }
+
+    /// 释放模板注册表句柄
+    #[no_mangle]
+    pub extern "C" fn destroy_registry(registry: *mut TemplateRegistry) {
+        if !registry.is_null() {
+            unsafe {
+                let _registry = Box::from_raw(registry);
             }
         }
+    }
+
+    /// 创建多页识别会话句柄，mark_ptr为MultiPageMark的JSON字符串
+    /// config_ptr为对所有页面生效的RecConfig，传空指针时使用各页Mark中的config字段或默认值
+    /// 句柄需通过destroy_session释放
+    #[no_mangle]
+    pub extern "C" fn create_session(
+        mark_ptr: *const c_char,
+        config_ptr: *const c_char,
+        session_out: *mut *mut ScanSession,
+    ) -> *mut c_char {
+        let mark_str = c_to_string(mark_ptr);
+        let session = c_to_config(config_ptr)
+            .and_then(|config| ScanSession::new(&mark_str, config));
+        export_handle(session, session_out)
+    }
 
-        let image = c_to_mat(data_ptr, data_len);
-        if image.is_err() {
-            failed_output.message = image.err().unwrap().to_string();
-            return CString::new(to_json(&failed_output).unwrap()).unwrap().into_raw();
+    /// 识别一页编码后的图片数据并记入会话，返回该页结果，页码自动识别
+    /// options_ptr为InferenceOptions的JSON字符串，传空指针使用默认选项
+    #[no_mangle]
+    pub extern "C" fn session_add_page(
+        session: *const ScanSession,
+        data_ptr: *const u8,
+        data_len: usize,
+        options_ptr: *const c_char,
+    ) -> *mut c_char {
+        let output = match (handle_to_ref(session), c_to_options(options_ptr)) {
+            (None, _) => MobileOutput::failed(ErrorCode::EngineNotInitialized, "会话句柄为空".to_string()),
+            (Some(_), Err(e)) => MobileOutput::from_error(&e),
+            (Some(session), Ok(options)) => run_session_page(session, c_to_mat(data_ptr, data_len), &options),
+        };
+        to_c_json(&output)
+    }
+
+    /// 合并会话中已扫描的页面，题号跨页连续；有页面未扫描时code为PagesIncomplete
+    #[no_mangle]
+    pub extern "C" fn session_result(session: *const ScanSession) -> *mut c_char {
+        let output = match handle_to_ref(session) {
+            Some(session) => session.merge(),
+            None => MobileOutput::failed(ErrorCode::EngineNotInitialized, "会话句柄为空".to_string()),
+        };
+        to_c_json(&output)
+    }
+
+    /// 清空会话中已扫描的页面，开始下一位考生
+    #[no_mangle]
+    pub extern "C" fn session_reset(session: *const ScanSession) {
+        if let Some(session) = handle_to_ref(session) {
+            session.reset();
         }
+    }
 
-        unsafe {
-            let engine = ENGINE.as_ref().unwrap();
-            let success_output = engine.inference(&image.unwrap());
-            if success_output.is_err() {
-                failed_output.message = success_output.err().unwrap().to_string();
-                return CString::new(to_json(&failed_output).unwrap()).unwrap().into_raw();
+    /// 释放多页识别会话句柄
+    #[no_mangle]
+    pub extern "C" fn destroy_session(session: *mut ScanSession) {
+        if !session.is_null() {
+            unsafe {
+                let _session = Box::from_raw(session);
+            }
+        }
+    }
+
+    /// 从空白答题卡的编码图片生成Mark草稿，不需要引擎句柄
+    /// config_ptr为RecConfig的JSON字符串，传空指针使用默认值
+    #[no_mangle]
+    pub extern "C" fn generate_mark(data_ptr: *const u8, data_len: usize, config_ptr: *const c_char) -> *mut c_char {
+        let mark = c_to_config(config_ptr).and_then(|config| {
+            let config = match config {
+                Some(config) => config.apply(&RecConfig::default())?,
+                None => RecConfig::default(),
+            };
+            config.validate()?;
+            let image = c_to_mat(data_ptr, data_len)?;
+            MarkGenerator::new(config).generate(&image)
+        });
+        let output = match mark {
+            Ok(mark) => MarkDraftOutput {
+                code: ErrorCode::Success.into(),
+                message: "success".to_string(),
+                mark: Some(mark),
+            },
+            Err(e) => MarkDraftOutput::from_error(&e),
+        };
+        to_c_json(&output)
+    }
+
+    /// 释放引擎句柄
+    #[no_mangle]
+    pub extern "C" fn destroy_engine(engine: *mut RecEngine) {
+        if !engine.is_null() {
+            unsafe {
+                let _engine = Box::from_raw(engine);
             }
-            return CString::new(to_json(&success_output.unwrap()).unwrap()).unwrap().into_raw();
         }
     }
 
@@ -101,8 +500,8 @@ pub mod build {
     pub extern "C" fn free_string(s: *mut c_char) {
         if !s.is_null() {
             unsafe {
-                let _cstring = CString::from_raw(s);
+                let _cstring = std::ffi::CString::from_raw(s);
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
-use opencv::core::Point2i as CvPoint2i;
+use opencv::core::{Point2f as CvPoint2f, Point2i as CvPoint2i};
+use crate::config::RecConfig;
+use crate::error::{error_code, ErrorCode};
+use crate::myutils::math::{distance, polygon_area};
 
 /// 坐标信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,8 +20,59 @@ pub struct Coordinate {
 /// 非矩形四边形
 #[derive(Debug, Clone)]
 pub struct Quad {
-    /// 四个顶点坐标
+    /// 四个顶点坐标，取整后用于绘制
     pub points: [CvPoint2i; 4],
+    /// 四个顶点的亚像素坐标，用于计算变换矩阵
+    pub corners: [CvPoint2f; 4],
+}
+
+impl Quad {
+    pub fn new(points: [CvPoint2i; 4]) -> Self {
+        Self {
+            points,
+            corners: points.map(|p| CvPoint2f::new(p.x as f32, p.y as f32)),
+        }
+    }
+
+    /// 使用亚像素顶点创建
+    pub fn from_corners(corners: [CvPoint2f; 4]) -> Self {
+        Self {
+            points: corners.map(|p| CvPoint2i::new(p.x.round() as i32, p.y.round() as i32)),
+            corners,
+        }
+    }
+
+    /// 顶点的浮点坐标
+    pub fn float_points(&self) -> [(f64, f64); 4] {
+        self.corners.map(|p| (p.x as f64, p.y as f64))
+    }
+
+    /// 对边平均长度，返回(宽, 高)
+    pub fn size(&self) -> (f64, f64) {
+        let [p0, p1, p2, p3] = self.float_points();
+        let w = (distance(p0.0, p0.1, p1.0, p1.1) + distance(p3.0, p3.1, p2.0, p2.1)) / 2.0;
+        let h = (distance(p1.0, p1.1, p2.0, p2.1) + distance(p0.0, p0.1, p3.0, p3.1)) / 2.0;
+        (w, h)
+    }
+
+    /// 四边形面积
+    pub fn area(&self) -> f64 {
+        polygon_area(&self.float_points())
+    }
+
+    /// 答题卡在图片中顺时针旋转了quarter_turns个90度时，按答题卡自身的左上角重新排列顶点
+    pub fn rotated(&self, quarter_turns: usize) -> Quad {
+        let mut corners = self.corners;
+        corners.rotate_left(quarter_turns % 4);
+        Quad::from_corners(corners)
+    }
+}
+
+/// 浮点坐标点
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Corner {
+    pub x: f64,
+    pub y: f64,
 }
 
 /// 轮廓信息，包含额外的检测数据
@@ -41,6 +95,34 @@ pub struct ProcessedImage {
     pub closed: opencv::core::Mat,
 }
 
+/// 对齐到模板空间的图片
+#[derive(Debug)]
+pub struct AlignedSheet {
+    /// 检测到的外部黑框
+    pub location: Quad,
+    /// 两次透视变换后的图片
+    pub warped: ProcessedImage,
+    /// 从缩放后的原图到模板空间的透视变换矩阵（两次变换合并）
+    pub transform: opencv::core::Mat,
+    /// 答题卡在图片中的顺时针旋转角度：0/90/180/270
+    pub orientation: i32,
+    /// 辅助定位点经第二次变换后与模板位置的均方根偏差（像素），不含误检点
+    pub assist_residual: f64,
+    /// 纸张弯曲时第二次变换使用的分段映射，此时transform只是近似
+    pub dewarp: Option<DewarpMaps>,
+}
+
+/// 分段映射
+#[derive(Debug)]
+pub struct DewarpMaps {
+    /// 从缩放后的原图到第一次变换结果的透视变换矩阵
+    pub boundary_transform: opencv::core::Mat,
+    /// 模板空间每个像素在第一次变换结果中的横坐标
+    pub map_x: opencv::core::Mat,
+    /// 模板空间每个像素在第一次变换结果中的纵坐标
+    pub map_y: opencv::core::Mat,
+}
+
 /// 识别类型枚举
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(from = "i32", into = "i32")]
@@ -49,6 +131,10 @@ pub enum RecType {
     SingleChoice = 1,
     /// 多选题
     MultipleChoice = 2,
+    /// 数字填涂网格，如准考证号
+    NumericGrid = 3,
+    /// 判断题，sub_options依次为"对"和"错"两个选项
+    Judgment = 4,
 }
 
 impl From<i32> for RecType {
@@ -56,6 +142,8 @@ impl From<i32> for RecType {
         match value {
             1 => RecType::SingleChoice,
             2 => RecType::MultipleChoice,
+            3 => RecType::NumericGrid,
+            4 => RecType::Judgment,
             _ => RecType::SingleChoice, // 默认值
         }
     }
@@ -67,25 +155,390 @@ impl From<RecType> for i32 {
     }
 }
 
+/// 选项判定策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "i32", into = "i32")]
+pub enum ChoicePolicy {
+    /// 每个选项独立与阈值比较
+    #[default]
+    Threshold = 1,
+    /// 只选填涂最明显的选项，多个选项难分高下时报告多选
+    Dominant = 2,
+}
+
+impl From<i32> for ChoicePolicy {
+    fn from(value: i32) -> Self {
+        match value {
+            2 => ChoicePolicy::Dominant,
+            _ => ChoicePolicy::Threshold, // 默认值
+        }
+    }
+}
+
+impl From<ChoicePolicy> for i32 {
+    fn from(policy: ChoicePolicy) -> Self {
+        policy as i32
+    }
+}
+
+impl RecType {
+    /// 未指定判定策略时的默认策略：所有类型都用阈值策略，保证多涂能被报告，主选项策略需在RecItem中显式指定
+    pub fn default_policy(&self) -> ChoicePolicy {
+        ChoicePolicy::Threshold
+    }
+
+    /// 是否只允许选一个选项
+    pub fn single_answer(&self) -> bool {
+        matches!(self, RecType::SingleChoice | RecType::Judgment)
+    }
+}
+
 /// 识别项目信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecItem {
-    /// 识别类型：1-单选，2-多选
+    /// 识别类型：1-单选，2-多选，3-数字网格，4-判断
     pub rec_type: RecType,
     /// 各个子选项的坐标
     pub sub_options: Vec<Coordinate>,
+    /// 判定策略：1-阈值，2-主选项，未指定时为1
+    /// 数字网格的策略作用于每一列
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<ChoicePolicy>,
+    /// 数字网格布局，rec_type为数字网格时必填
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<GridSpec>,
+}
+
+/// 数字网格布局
+/// sub_options按列依次排列，每列自上而下为0-9，带空白选项时空白选项排在该列最后
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GridSpec {
+    /// 列数，即数字位数
+    pub columns: usize,
+    /// 每列是否带空白选项
+    #[serde(default)]
+    pub blank_option: bool,
+}
+
+impl GridSpec {
+    /// 每列的选项数
+    pub fn rows(&self) -> usize {
+        if self.blank_option { 11 } else { 10 }
+    }
 }
 
 /// 标注信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mark {
-    /// 外围矩形边框
+    /// 外围矩形边框；使用四角定位块时为四个定位块中心围成的矩形
     pub boundary: Coordinate,
+    /// 定位方式：1-外部黑框，2-四角定位块，默认为1
+    #[serde(default)]
+    pub locator: LocatorStrategy,
     /// 需要识别的项目
     pub rec_items: Vec<RecItem>,
     /// 辅助定位
     pub assist_location: AssistLocation,
+    /// 识别参数，未指定时使用默认值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<RecConfig>,
+    /// 二维码/条形码区域
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub code_regions: Vec<CodeRegion>,
+    /// 需要截图返回的自由作答区域，如作文框、姓名栏
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crop_regions: Vec<CropRegion>,
+    /// 监考填涂的状态标记，如缺考、违纪
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status_flags: Vec<StatusFlag>,
+    /// 卡面上印刷的模板ID，用于多模板时识别模板
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+    /// 印刷模板ID的二维码/条形码区域
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_id_region: Option<CodeRegion>,
+    /// 页码标识：只印在本页该位置的实心黑块，用于区分版式相同的多页
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_indicator: Option<Coordinate>,
+    /// 方向标记：印在模板一角的实心黑块，用于判断答题卡是否旋转或倒置
+    /// 辅助定位点上下对称时无法区分0度和180度，需要声明方向标记
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation_mark: Option<Coordinate>,
+    /// 标准答案，推理选项中也可传入，推理选项优先
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer_key: Option<AnswerKey>,
+}
+
+/// 标准答案
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnswerKey {
+    /// 对应rec_items的答案，不计分的项目填null
+    pub questions: Vec<Option<KeyQuestion>>,
+    /// 多选题的默认评分规则
+    #[serde(default)]
+    pub multiple_rule: PartialCreditRule,
+}
+
+/// 单题标准答案
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyQuestion {
+    /// 正确选项在sub_options中的下标，判断题0为对、1为错
+    #[serde(default)]
+    pub answer: Vec<usize>,
+    /// 数字网格的正确字符串
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// 满分
+    pub points: f64,
+    /// 多选题少选时的得分，未指定时为满分的一半
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial_points: Option<f64>,
+    /// 本题的多选评分规则，未指定时使用AnswerKey.multiple_rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<PartialCreditRule>,
+}
+
+/// 多选题评分规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "i32", into = "i32")]
+pub enum PartialCreditRule {
+    /// 全对得满分，否则不得分
+    AllOrNothing = 1,
+    /// 全对得满分，少选且没有错选得部分分，有错选不得分
+    #[default]
+    PartialSubset = 2,
+}
+
+impl From<i32> for PartialCreditRule {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => PartialCreditRule::AllOrNothing,
+            _ => PartialCreditRule::PartialSubset, // 默认值
+        }
+    }
+}
+
+impl From<PartialCreditRule> for i32 {
+    fn from(rule: PartialCreditRule) -> Self {
+        rule as i32
+    }
+}
+
+impl AnswerKey {
+    /// 检查标准答案与Mark的识别项目是否对应
+    pub fn validate(&self, mark: &Mark) -> anyhow::Result<()> {
+        if self.questions.len() != mark.rec_items.len() {
+            crate::bail_code!(
+                ErrorCode::AnswerKeyInvalid,
+                "标准答案题数{}与识别项目数{}不一致",
+                self.questions.len(), mark.rec_items.len()
+            );
+        }
+        for (index, (question, rec_item)) in self.questions.iter().zip(mark.rec_items.iter()).enumerate() {
+            let Some(question) = question else {
+                continue;
+            };
+            if question.points < 0.0 || question.partial_points.is_some_and(|partial| !(0.0..=question.points).contains(&partial)) {
+                crate::bail_code!(ErrorCode::AnswerKeyInvalid, "第{}题分值不合法", index);
+            }
+            if rec_item.rec_type == RecType::NumericGrid {
+                if question.text.is_none() {
+                    crate::bail_code!(ErrorCode::AnswerKeyInvalid, "第{}题是数字网格，缺少text答案", index);
+                }
+                continue;
+            }
+            if question.answer.is_empty() || question.answer.iter().any(|&option| option >= rec_item.sub_options.len()) {
+                crate::bail_code!(ErrorCode::AnswerKeyInvalid, "第{}题的答案选项为空或超出范围", index);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 多页答题卡模板，每页是一个独立的Mark，题号按页顺序连续编排
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiPageMark {
+    pub pages: Vec<Mark>,
+}
+
+/// 定位方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "i32", into = "i32")]
+pub enum LocatorStrategy {
+    /// 印刷的粗外框
+    #[default]
+    Frame = 1,
+    /// 四个角上的实心方块
+    CornerFiducials = 2,
+}
+
+impl From<i32> for LocatorStrategy {
+    fn from(value: i32) -> Self {
+        match value {
+            2 => LocatorStrategy::CornerFiducials,
+            _ => LocatorStrategy::Frame, // 默认值
+        }
+    }
+}
+
+impl From<LocatorStrategy> for i32 {
+    fn from(locator: LocatorStrategy) -> Self {
+        locator as i32
+    }
+}
+
+/// 模板注册表中的一个模板
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateEntry {
+    /// 模板名称，识别结果中原样返回
+    pub name: String,
+    pub mark: Mark,
+}
+
+/// 状态标记
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusFlag {
+    /// 标记名称，原样返回
+    #[serde(default)]
+    pub name: String,
+    /// 标记类型：1-缺考，2-违纪，3-其他
+    #[serde(default)]
+    pub kind: FlagKind,
+    /// 模板空间中的填涂框
+    pub coordinate: Coordinate,
+}
+
+/// 状态标记类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "i32", into = "i32")]
+pub enum FlagKind {
+    /// 缺考，填涂后不输出作答结果
+    Absent = 1,
+    /// 违纪
+    Disciplinary = 2,
+    /// 其他自定义标记
+    #[default]
+    Other = 3,
+}
+
+impl From<i32> for FlagKind {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => FlagKind::Absent,
+            2 => FlagKind::Disciplinary,
+            _ => FlagKind::Other, // 默认值
+        }
+    }
+}
+
+impl From<FlagKind> for i32 {
+    fn from(kind: FlagKind) -> Self {
+        kind as i32
+    }
+}
+
+/// 截图区域
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CropRegion {
+    /// 区域名称，原样返回，便于调用方区分
+    #[serde(default)]
+    pub name: String,
+    /// 模板空间中的区域
+    pub coordinate: Coordinate,
+    /// 是否截取彩色图，默认截取灰度图
+    #[serde(default)]
+    pub color: bool,
+}
+
+/// 二维码/条形码区域
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeRegion {
+    /// 区域名称，原样返回，便于调用方区分
+    #[serde(default)]
+    pub name: String,
+    /// 码的类型：1-二维码，2-条形码
+    #[serde(default)]
+    pub code_type: CodeType,
+    /// 模板空间中的区域
+    pub coordinate: Coordinate,
+}
+
+/// 码的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "i32", into = "i32")]
+pub enum CodeType {
+    /// 二维码
+    #[default]
+    Qr = 1,
+    /// 一维条形码
+    Barcode = 2,
+}
+
+impl From<i32> for CodeType {
+    fn from(value: i32) -> Self {
+        match value {
+            2 => CodeType::Barcode,
+            _ => CodeType::Qr, // 默认值
+        }
+    }
 }
+
+impl From<CodeType> for i32 {
+    fn from(code_type: CodeType) -> Self {
+        code_type as i32
+    }
+}
+
+impl Mark {
+    /// 检查识别项目的布局是否自洽
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (index, rec_item) in self.rec_items.iter().enumerate() {
+            // 宽高为0的选项框填涂率为NaN
+            if rec_item.sub_options.iter().any(|option| option.w <= 0 || option.h <= 0) {
+                crate::bail_code!(ErrorCode::MarkJsonInvalid, "第{}个识别项目存在宽高不为正的选项", index);
+            }
+            if rec_item.rec_type == RecType::Judgment && rec_item.sub_options.len() != 2 {
+                crate::bail_code!(ErrorCode::MarkJsonInvalid, "第{}个识别项目是判断题，选项数必须为2", index);
+            }
+            if rec_item.rec_type != RecType::NumericGrid {
+                continue;
+            }
+            let Some(grid) = rec_item.grid else {
+                crate::bail_code!(ErrorCode::MarkJsonInvalid, "第{}个识别项目是数字网格，但缺少grid字段", index);
+            };
+            if grid.columns == 0 || rec_item.sub_options.len() != grid.columns * grid.rows() {
+                crate::bail_code!(
+                    ErrorCode::MarkJsonInvalid,
+                    "第{}个识别项目的选项数{}与数字网格{}列x{}行不符",
+                    index, rec_item.sub_options.len(), grid.columns, grid.rows()
+                );
+            }
+        }
+        for flag in self.status_flags.iter() {
+            self.check_region("状态标记", &flag.name, &flag.coordinate)?;
+        }
+        for crop_region in self.crop_regions.iter() {
+            self.check_region("截图区域", &crop_region.name, &crop_region.coordinate)?;
+        }
+        for code_region in self.code_regions.iter() {
+            self.check_region("条码区域", &code_region.name, &code_region.coordinate)?;
+        }
+        Ok(())
+    }
+
+    /// 检查区域宽高为正且完全落在模板空间内，模板空间即矫正后的图片
+    fn check_region(&self, kind: &str, name: &str, coordinate: &Coordinate) -> anyhow::Result<()> {
+        let width = self.boundary.x + self.boundary.w;
+        let height = self.boundary.y + self.boundary.h;
+        if coordinate.w <= 0 || coordinate.h <= 0
+            || coordinate.x < 0 || coordinate.y < 0
+            || coordinate.x + coordinate.w > width || coordinate.y + coordinate.h > height {
+            crate::bail_code!(ErrorCode::MarkJsonInvalid, "{}{}超出模板范围{}x{}", kind, name, width, height);
+        }
+        Ok(())
+    }
+}
+
 /// 辅助定位点
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssistLocation {
@@ -99,8 +552,76 @@ pub struct RecResult {
     /// 对应输入的sub_options，true表示选中，false表示未选中
     pub rec_result: Vec<bool>,
     pub fill_items: Vec<FillItem>,
-    pub rec_tpye: RecType
+    pub rec_tpye: RecType,
+    /// 作答状态
+    #[serde(default)]
+    pub status: RecStatus,
+    /// 判定策略，来自RecItem
+    #[serde(skip)]
+    pub policy: ChoicePolicy,
+    /// 数字网格布局，来自RecItem
+    #[serde(skip)]
+    pub grid: Option<GridSpec>,
+    /// 数字网格识别出的字符串，未识别或多涂的位置为'?'，填涂空白选项的位置不输出字符
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// 数字网格每一位的识别结果
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub positions: Vec<GridPosition>,
+    /// 判断题的识别结果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub judgment: Option<Judgment>,
+    /// 题目置信度，0-1，由各选项到阈值的距离和前两名选项的差距决定
+    #[serde(default)]
+    pub confidence: f64,
+    /// 没有选项被选中
+    #[serde(default)]
+    pub blank: bool,
+    /// 单选题有多个选项被选中
+    #[serde(default)]
+    pub multiple_marked_on_single: bool,
+    /// 存在填涂率接近阈值的选项
+    #[serde(default)]
+    pub borderline: bool,
+    /// 多页合并后的题号，从1开始跨页连续编号
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub question_no: Option<usize>,
+}
+
+/// 判断题识别结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Judgment {
+    /// 选了"对"
+    True,
+    /// 选了"错"
+    False,
+    /// 未作答
+    Blank,
+    /// 两个选项都被填涂，无法确定
+    Conflict,
+}
+
+/// 数字网格单列的识别结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridPosition {
+    /// 识别出的数字，未作答、多涂或填涂空白选项时为空
+    pub digit: Option<u8>,
+    /// 该列的作答状态，填涂空白选项视为已作答
+    pub status: RecStatus,
+}
 
+/// 作答状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecStatus {
+    /// 已作答
+    Answered,
+    /// 未作答
+    #[default]
+    Blank,
+    /// 单选题多个选项填涂程度相当，无法确定答案
+    MultipleAnswers,
 }
 
 /// 填涂率结果
@@ -109,16 +630,210 @@ pub struct FillItem {
     /// 对应输入的sub_options，true表示选中，false表示未选中
     pub fill_rate: f64,
     pub coordinate: Coordinate,
+    /// 选项置信度，0-1，由填涂率到阈值的距离决定
+    #[serde(default)]
+    pub confidence: f64,
+    /// 灰度图上的平均深浅，0-1，越大越黑，仅在擦除识别模式下计算
+    #[serde(default)]
+    pub darkness: f64,
+    /// 填涂状态
+    #[serde(default)]
+    pub state: MarkState,
+}
+
+/// 选项填涂状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkState {
+    /// 未填涂
+    #[default]
+    Empty,
+    /// 填涂后被擦除
+    Erased,
+    /// 已填涂
+    Filled,
 }
 
 /// 输出数据结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MobileOutput {
-    /// 识别状态：0-成功，1-失败
+    /// 识别状态：0-成功，其他取值见ErrorCode
     pub code: i32,
     pub message: String,
     /// 对应输入的rec_items的识别结果
-    pub rec_results: Vec<RecResult>
+    pub rec_results: Vec<RecResult>,
+    /// 填涂率阈值
+    #[serde(default)]
+    pub fill_threshold: f64,
+    /// 多帧融合时参与融合的帧序号
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub used_frames: Vec<usize>,
+    /// 多帧融合时被剔除的帧
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected_frames: Vec<RejectedFrame>,
+    /// 调试图片，仅在推理选项开启调试输出时返回
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub debug_images: Vec<DebugImage>,
+    /// 调试图片导出失败的原因，导出失败不影响识别结果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_error: Option<String>,
+    /// 对应输入的code_regions的识别结果
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub codes: Vec<CodeResult>,
+    /// 对应输入的crop_regions的截图
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crops: Vec<RegionCrop>,
+    /// 缺考，为true时rec_results中的作答结果被清空
+    #[serde(default)]
+    pub absent: bool,
+    /// 违纪
+    #[serde(default)]
+    pub disciplinary: bool,
+    /// 对应输入的status_flags的识别结果
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<FlagResult>,
+    /// 多模板识别时匹配到的模板名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// 多模板识别时的匹配分数，0-1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_score: Option<f64>,
+    /// 多页识别时的页码，从1开始
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+    /// 多页合并时尚未扫描的页码
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_pages: Vec<usize>,
+    /// 对应rec_results的得分，提供标准答案时计算，不计分的项目为null
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scores: Vec<Option<QuestionScore>>,
+    /// 总分
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_score: Option<f64>,
+    /// 满分
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_score: Option<f64>,
+    /// 答题卡在图片中的顺时针旋转角度：0/90/180/270，识别前已自动摆正
+    #[serde(default)]
+    pub orientation: i32,
+}
+
+/// 单题得分
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionScore {
+    pub score: f64,
+    /// 本题满分
+    pub points: f64,
+    pub outcome: ScoreOutcome,
+}
+
+/// 判分结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreOutcome {
+    /// 全对
+    Correct,
+    /// 少选得部分分
+    Partial,
+    /// 错误或多涂
+    Wrong,
+    /// 未作答
+    Blank,
+}
+
+/// 状态标记识别结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagResult {
+    /// 标记名称
+    pub name: String,
+    /// 标记类型
+    pub kind: FlagKind,
+    /// 填涂率
+    pub fill_rate: f64,
+    /// 是否被填涂
+    pub marked: bool,
+    pub coordinate: Coordinate,
+}
+
+/// 区域截图
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionCrop {
+    /// 区域名称
+    pub name: String,
+    /// 图片编码格式
+    pub format: String,
+    /// base64编码的图片数据
+    pub data: String,
+}
+
+/// 二维码/条形码识别结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeResult {
+    /// 区域名称
+    pub name: String,
+    /// 码的类型
+    pub code_type: CodeType,
+    /// 识别出的内容，识别失败时为空
+    pub text: Option<String>,
+    /// 是否在未摆正的原图上识别得到
+    #[serde(default)]
+    pub from_full_image: bool,
+}
+
+/// 调试图片
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugImage {
+    /// 图片名称
+    pub name: String,
+    /// base64编码的图片数据，未要求返回时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// 写入文件的路径，未指定目录时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// 推理选项，c接口以JSON传入，所有字段均可省略
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InferenceOptions {
+    /// 调试输出，为空时不输出
+    pub debug: Option<DebugOptions>,
+    /// 标准答案，优先于Mark中的answer_key
+    pub answer_key: Option<AnswerKey>,
+}
+
+/// 调试输出选项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebugOptions {
+    /// 是否在结果中返回base64编码的图片
+    pub return_images: bool,
+    /// 图片保存目录，为空时不写文件
+    pub output_dir: Option<String>,
+    /// 图片编码格式
+    pub format: String,
+}
+
+impl Default for DebugOptions {
+    fn default() -> Self {
+        Self {
+            return_images: true,
+            output_dir: None,
+            format: ".jpg".to_string(),
+        }
+    }
+}
+
+/// 多帧融合中被剔除的帧
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedFrame {
+    /// 帧序号
+    pub index: usize,
+    /// 错误码，取值见ErrorCode
+    pub code: i32,
+    /// 剔除原因
+    pub reason: String,
 }
 
 impl MobileOutput {
@@ -135,17 +850,257 @@ impl MobileOutput {
                         |coordinate| FillItem {
                             fill_rate: 0.0,
                             coordinate: coordinate.clone(),
+                            confidence: 0.0,
+                            darkness: 0.0,
+                            state: MarkState::Empty,
                         }
                     ).collect(),
-                    rec_tpye: rec_item.rec_type
+                    rec_tpye: rec_item.rec_type,
+                    status: RecStatus::Blank,
+                    policy: rec_item.policy.unwrap_or_else(|| rec_item.rec_type.default_policy()),
+                    grid: rec_item.grid,
+                    text: None,
+                    positions: vec![],
+                    judgment: None,
+                    confidence: 0.0,
+                    blank: true,
+                    multiple_marked_on_single: false,
+                    borderline: false,
+                    question_no: None,
                 }
             })
             .collect();
 
+        let flags = mark.status_flags
+            .iter()
+            .map(|status_flag| FlagResult {
+                name: status_flag.name.clone(),
+                kind: status_flag.kind,
+                fill_rate: 0.0,
+                marked: false,
+                coordinate: status_flag.coordinate.clone(),
+            })
+            .collect();
+
         MobileOutput {
             code: 0, // 默认状态为成功
             message: "success".to_string(),
             rec_results,
+            fill_threshold: 0.0,
+            used_frames: vec![],
+            rejected_frames: vec![],
+            debug_images: vec![],
+            debug_error: None,
+            codes: vec![],
+            crops: vec![],
+            absent: false,
+            disciplinary: false,
+            template: None,
+            match_score: None,
+            page: None,
+            missing_pages: vec![],
+            scores: vec![],
+            total_score: None,
+            max_score: None,
+            orientation: 0,
+            flags,
+        }
+    }
+
+    /// 创建一个没有识别结果的成功实例，用于合并多页结果
+    pub fn empty() -> Self {
+        MobileOutput {
+            code: ErrorCode::Success.into(),
+            message: "success".to_string(),
+            rec_results: vec![],
+            fill_threshold: 0.0,
+            used_frames: vec![],
+            rejected_frames: vec![],
+            debug_images: vec![],
+            debug_error: None,
+            codes: vec![],
+            crops: vec![],
+            absent: false,
+            disciplinary: false,
+            template: None,
+            match_score: None,
+            page: None,
+            missing_pages: vec![],
+            scores: vec![],
+            total_score: None,
+            max_score: None,
+            orientation: 0,
+            flags: vec![],
+        }
+    }
+
+    /// 创建一个失败的MobileOutput实例
+    pub fn failed(code: ErrorCode, message: String) -> Self {
+        MobileOutput {
+            code: code.into(),
+            message,
+            ..Self::empty()
+        }
+    }
+
+    /// 根据错误创建一个失败的MobileOutput实例
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        Self::failed(error_code(err), err.to_string())
+    }
+
+    /// 清空所有作答结果，保留填涂率便于核查
+    pub fn suppress_answers(&mut self) {
+        for rec_result in self.rec_results.iter_mut() {
+            rec_result.rec_result.iter_mut().for_each(|selected| *selected = false);
+            rec_result.status = RecStatus::Blank;
+            rec_result.blank = true;
+            rec_result.multiple_marked_on_single = false;
+            rec_result.text = None;
+            rec_result.positions.clear();
+            rec_result.judgment = None;
+        }
+    }
+}
+
+/// 模板生成结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkDraftOutput {
+    /// 生成状态：0-成功，其他取值见ErrorCode
+    pub code: i32,
+    pub message: String,
+    /// 生成的Mark草稿，需人工确认题型和选项后使用
+    pub mark: Option<Mark>,
+}
+
+impl MarkDraftOutput {
+    /// 创建一个失败的MarkDraftOutput实例
+    pub fn failed(code: ErrorCode, message: String) -> Self {
+        MarkDraftOutput {
+            code: code.into(),
+            message,
+            mark: None,
+        }
+    }
+
+    /// 根据错误创建一个失败的MarkDraftOutput实例
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        Self::failed(error_code(err), err.to_string())
+    }
+}
+
+/// 标准答案采集结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyCaptureOutput {
+    /// 采集状态：0-成功，其他取值见ErrorCode
+    pub code: i32,
+    pub message: String,
+    /// 采集到的标准答案，存在不确定的题目时为空
+    pub answer_key: Option<AnswerKey>,
+    /// 不确定的题目，需要老师修改答案卡后重新扫描
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ambiguous: Vec<AmbiguousQuestion>,
+}
+
+/// 采集标准答案时不确定的题目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmbiguousQuestion {
+    /// 对应rec_items的下标
+    pub index: usize,
+    /// 题目置信度
+    pub confidence: f64,
+    /// 原因
+    pub reason: String,
+}
+
+impl KeyCaptureOutput {
+    /// 创建一个失败的KeyCaptureOutput实例
+    pub fn failed(code: ErrorCode, message: String) -> Self {
+        KeyCaptureOutput {
+            code: code.into(),
+            message,
+            answer_key: None,
+            ambiguous: vec![],
+        }
+    }
+
+    /// 根据错误创建一个失败的KeyCaptureOutput实例
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        Self::failed(error_code(err), err.to_string())
+    }
+}
+
+/// 预览检查结果，用于拍摄引导
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewOutput {
+    /// 检测状态：0-成功，其他取值见ErrorCode
+    pub code: i32,
+    pub message: String,
+    /// 外部黑框四个顶点（原图坐标），顺时针，从左上角开始
+    pub corners: Vec<Corner>,
+    /// 与Mark.boundary宽高比的吻合度，0-1
+    pub aspect_score: f64,
+    /// 外部黑框面积占画面比例
+    pub area_ratio: f64,
+    /// 辅助定位点数量是否正确，未检查时为空
+    pub assist_ok: Option<bool>,
+    /// 就绪分数，0-1
+    pub readiness: f64,
+    /// 是否可以拍摄
+    pub ready: bool,
+    /// 外框校验未通过的原因，取值见ErrorCode，0表示通过
+    #[serde(default)]
+    pub guidance_code: i32,
+    /// 外框校验未通过时的拍摄建议，如靠近、拉远或正对答题卡
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guidance: Option<String>,
+}
+
+impl PreviewOutput {
+    /// 创建一个失败的PreviewOutput实例
+    pub fn failed(code: ErrorCode, message: String) -> Self {
+        PreviewOutput {
+            code: code.into(),
+            message,
+            corners: vec![],
+            aspect_score: 0.0,
+            area_ratio: 0.0,
+            assist_ok: None,
+            readiness: 0.0,
+            ready: false,
+            guidance_code: 0,
+            guidance: None,
+        }
+    }
+
+    /// 根据错误创建一个失败的PreviewOutput实例
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        Self::failed(error_code(err), err.to_string())
+    }
+}
+
+/// 原始像素格式，c接口
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Android相机默认格式，Y平面后紧跟VU交错平面
+    Nv21 = 1,
+    /// I420格式，Y平面后依次为U、V平面
+    Yuv420 = 2,
+    /// iOS相机默认格式
+    Bgra = 3,
+    Rgba = 4,
+}
+
+impl TryFrom<i32> for PixelFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(PixelFormat::Nv21),
+            2 => Ok(PixelFormat::Yuv420),
+            3 => Ok(PixelFormat::Bgra),
+            4 => Ok(PixelFormat::Rgba),
+            _ => anyhow::bail!("不支持的像素格式: {}", value),
         }
     }
 }
@@ -153,6 +1108,66 @@ impl MobileOutput {
 /// 初始化状态，c接口
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InitInfo {
-    pub code: u8,
+    /// 初始化状态：0-成功，其他取值见ErrorCode
+    pub code: i32,
     pub message: String
 }
+
+#[cfg(test)]
+impl Mark {
+    /// 构造测试用的Mark，每个元素为(识别类型, 选项数)，选项在同一行横向排列
+    pub(crate) fn for_test(items: &[(i32, usize)]) -> Mark {
+        let rec_items = items.iter().map(|&(rec_type, count)| {
+            let sub_options = (0..count)
+                .map(|i| format!(r#"{{"x": {}, "y": 10, "w": 10, "h": 10}}"#, 10 + i * 20))
+                .collect::<Vec<String>>()
+                .join(",");
+            format!(r#"{{"rec_type": {}, "sub_options": [{}]}}"#, rec_type, sub_options)
+        }).collect::<Vec<String>>().join(",");
+        crate::myutils::myjson::from_json(&format!(
+            r#"{{"boundary": {{"x": 0, "y": 0, "w": 200, "h": 200}}, "rec_items": [{}], "assist_location": {{"left": [], "right": []}}}}"#,
+            rec_items
+        )).unwrap()
+    }
+}
+
+#[cfg(test)]
+impl MobileOutput {
+    /// 按题目顺序设置各选项的填涂率
+    pub(crate) fn set_fill_rates(&mut self, rates: &[&[f64]]) {
+        for (rec_result, rates) in self.rec_results.iter_mut().zip(rates.iter()) {
+            for (fill_item, &rate) in rec_result.fill_items.iter_mut().zip(rates.iter()) {
+                fill_item.fill_rate = rate;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::error_code;
+
+    #[test]
+    fn test_validate_option_size() {
+        let mut mark = Mark::for_test(&[(1, 4)]);
+        assert!(mark.validate().is_ok());
+
+        mark.rec_items[0].sub_options[2].w = 0;
+        assert_eq!(error_code(&mark.validate().unwrap_err()), ErrorCode::MarkJsonInvalid);
+    }
+
+    #[test]
+    fn test_validate_crop_region() {
+        let mut mark = Mark::for_test(&[(1, 4)]);
+        mark.crop_regions.push(CropRegion {
+            name: "姓名".to_string(),
+            coordinate: Coordinate { x: 150, y: 150, w: 50, h: 50 },
+            color: false,
+        });
+        assert!(mark.validate().is_ok());
+
+        mark.crop_regions[0].coordinate.x = 160;
+        assert_eq!(error_code(&mark.validate().unwrap_err()), ErrorCode::MarkJsonInvalid);
+    }
+}
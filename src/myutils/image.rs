@@ -1,5 +1,5 @@
 use opencv::{
-    core::{AlgorithmHint, Mat, Point2i, Point2f, Size, Vector},
+    core::{AlgorithmHint, Mat, Point2i, Point2f, Scalar, Size, Vector, CV_32FC1},
     calib3d,
     imgcodecs::{imdecode, imread, IMREAD_COLOR},
     imgproc,
@@ -7,8 +7,10 @@ use opencv::{
 };
 use base64::{Engine as _, engine::general_purpose};
 use anyhow::{Result, Context};
-use crate::models::{AssistLocation, Coordinate, ProcessedImage, Quad};
-use crate::config::ImageProcessingConfig;
+use crate::models::{AssistLocation, Coordinate, DebugImage, DebugOptions, ProcessedImage, Quad, RegionCrop};
+use crate::config::{AssistLocationConfig, CropConfig, ImageProcessingConfig};
+use crate::error::{ErrorCode, WithCode};
+use crate::myutils::math::{distance, median};
 
 pub fn read_image(input: &String) -> Result<Mat> {
     // 判断输入是文件路径还是base64字符串
@@ -54,9 +56,9 @@ pub fn resize_image(image: &Mat, target_width: i32) -> Result<Mat> {
 }
 
 /// 图片预处理：灰度化、高斯模糊、二值化、形态学操作
-pub fn process_image(image: &Mat) -> Result<ProcessedImage> {
+pub fn process_image(image: &Mat, config: &ImageProcessingConfig) -> Result<ProcessedImage> {
     // 0. 图片统一到宽度
-    let resized = resize_image(image, ImageProcessingConfig::TARGET_WIDTH)?;
+    let resized = resize_image(image, config.target_width)?;
 
     // 1. 灰度化
     let mut gray = Mat::default();
@@ -64,8 +66,8 @@ pub fn process_image(image: &Mat) -> Result<ProcessedImage> {
 
     // 2. 高斯模糊
     let mut blur = Mat::default();
-    let kernel_size = Size::new(ImageProcessingConfig::GAUSSIAN_KERNEL_SIZE, ImageProcessingConfig::GAUSSIAN_KERNEL_SIZE);
-    imgproc::gaussian_blur(&gray, &mut blur, kernel_size, ImageProcessingConfig::GAUSSIAN_SIGMA, ImageProcessingConfig::GAUSSIAN_SIGMA, opencv::core::BORDER_DEFAULT, AlgorithmHint::ALGO_HINT_DEFAULT)?;
+    let kernel_size = Size::new(config.gaussian_kernel_size, config.gaussian_kernel_size);
+    imgproc::gaussian_blur(&gray, &mut blur, kernel_size, config.gaussian_sigma, config.gaussian_sigma, opencv::core::BORDER_DEFAULT, AlgorithmHint::ALGO_HINT_DEFAULT)?;
 
     // 3. 自适应阈值二值化
     let mut thresh = Mat::default();
@@ -75,14 +77,14 @@ pub fn process_image(image: &Mat) -> Result<ProcessedImage> {
         255.0,
         imgproc::ADAPTIVE_THRESH_GAUSSIAN_C,
         imgproc::THRESH_BINARY_INV,
-        ImageProcessingConfig::BLOCK_SIZE,
-        ImageProcessingConfig::C as f64,
+        config.block_size,
+        config.c as f64,
     )?;
 
     // 4. 形态学闭操作
     let kernel = imgproc::get_structuring_element(
         imgproc::MORPH_ELLIPSE,
-        Size::new(ImageProcessingConfig::MORPH_KERNEL, ImageProcessingConfig::MORPH_KERNEL),
+        Size::new(config.morph_kernel, config.morph_kernel),
         Point2i::new(-1, -1),
     )?;
     let mut closed = Mat::default();
@@ -117,7 +119,7 @@ pub fn get_perspective_transform_matrix_with_boundary(
 
     // 计算透视变换矩阵
     let transform_matrix = imgproc::get_perspective_transform(&src_points, &target_points, 0)
-        .context("计算透视变换矩阵失败")?;
+        .with_code(ErrorCode::HomographyFailed, "计算透视变换矩阵失败")?;
 
     Ok(transform_matrix)
 }
@@ -141,23 +143,144 @@ pub fn get_perspective_transform_matrix_with_assists(
         &target_points,
         &mut mask,
         calib3d::RANSAC,  // 也可用 calib3d::LMEDS 或 0
-        3.0,               // ransac_reproj_threshold (像素)
+        AssistLocationConfig::RANSAC_THRESHOLD,
     )
-    .context("使用 RANSAC 计算透视变换矩阵失败")?;
+    .with_code(ErrorCode::HomographyFailed, "使用 RANSAC 计算透视变换矩阵失败")?;
+
+    // 点集退化时findHomography返回空矩阵而不是报错
+    if transform_matrix.empty() {
+        crate::bail_code!(ErrorCode::HomographyFailed, "使用 RANSAC 计算透视变换矩阵失败: 辅助定位点退化");
+    }
 
     Ok(transform_matrix)
 }
 
+/// 每个辅助定位点经透视变换后与模板位置的偏差（像素），顺序为左侧各点后接右侧各点
+pub fn assist_residuals(
+    src_assists: &AssistLocation,
+    target_assists: &AssistLocation,
+    transform_matrix: &Mat,
+) -> Result<Vec<f64>> {
+    let src_points = get_points_from_coordinates(&[src_assists.left.clone(), src_assists.right.clone()].concat());
+    let target_points = get_points_from_coordinates(&[target_assists.left.clone(), target_assists.right.clone()].concat());
+    let mut projected = Vector::<Point2f>::new();
+    opencv::core::perspective_transform(&src_points, &mut projected, transform_matrix)?;
+
+    Ok(projected.iter().zip(target_points.iter())
+        .map(|(p, t)| distance(p.x as f64, p.y as f64, t.x as f64, t.y as f64))
+        .collect())
+}
+
+/// 剔除误检的辅助定位点对，返回其余点的均方根偏差和保留的定位点对下标
+/// 偏差超过中位数的OUTLIER_RESIDUAL_FACTOR倍且超过RANSAC阈值的点视为误检，左右任一点误检则整对剔除；
+/// 没有保留的定位点对时返回偏差中位数
+pub fn residual_inliers(residuals: &[f64], pair_count: usize) -> (f64, Vec<usize>) {
+    let median_residual = median(residuals);
+    let limit = (median_residual * AssistLocationConfig::OUTLIER_RESIDUAL_FACTOR).max(AssistLocationConfig::RANSAC_THRESHOLD);
+    let inliers = (0..pair_count)
+        .filter(|&i| residuals[i] <= limit && residuals[pair_count + i] <= limit)
+        .collect::<Vec<usize>>();
+    if inliers.is_empty() {
+        return (median_residual, inliers);
+    }
+
+    let sum_squares = inliers.iter()
+        .map(|&i| residuals[i].powi(2) + residuals[pair_count + i].powi(2))
+        .sum::<f64>();
+    ((sum_squares / (inliers.len() * 2) as f64).sqrt(), inliers)
+}
+
+/// 分段映射：相邻两对辅助定位点之间的条带各用一个透视变换，生成从模板空间到源图的remap映射表
+/// 只使用pairs中的辅助定位点对，第一对之上和最后一对之下的区域沿用相邻条带的变换
+pub fn piecewise_maps(
+    src_assists: &AssistLocation,
+    target_assists: &AssistLocation,
+    pairs: &[usize],
+    target_w: i32,
+    target_h: i32,
+) -> Result<(Mat, Mat)> {
+    // 1. 按模板中的纵坐标排序辅助定位点对
+    let mut pairs = pairs.to_vec();
+    pairs.sort_by_key(|&i| target_assists.left[i].y + target_assists.right[i].y);
+    if pairs.len() < 2 {
+        crate::bail_code!(ErrorCode::HomographyFailed, "分段映射至少需要两对辅助定位点，实际{}对", pairs.len());
+    }
+
+    // 2. 每个条带从模板空间到源图的透视变换，条带以下方一对辅助定位点的中心为下边界
+    let mut bands = Vec::with_capacity(pairs.len() - 1);
+    for window in pairs.windows(2) {
+        let (top, bottom) = (window[0], window[1]);
+        let band_points = |assists: &AssistLocation| get_points_from_coordinates(&vec![
+            assists.left[top].clone(), assists.right[top].clone(), assists.right[bottom].clone(), assists.left[bottom].clone(),
+        ]);
+        let target_points = band_points(target_assists);
+        let matrix = imgproc::get_perspective_transform(&target_points, &band_points(src_assists), 0)
+            .with_code(ErrorCode::HomographyFailed, "计算分段透视变换矩阵失败")?;
+        let mut h = [0.0; 9];
+        for (i, value) in h.iter_mut().enumerate() {
+            *value = *matrix.at_2d::<f64>(i as i32 / 3, i as i32 % 3)?;
+        }
+        let bottom_y = (target_points.get(2)?.y + target_points.get(3)?.y) as f64 / 2.0;
+        bands.push((bottom_y, h));
+    }
+
+    // 3. 逐像素计算在源图中的坐标
+    let mut map_x = Mat::new_rows_cols_with_default(target_h, target_w, CV_32FC1, Scalar::all(0.0))?;
+    let mut map_y = Mat::new_rows_cols_with_default(target_h, target_w, CV_32FC1, Scalar::all(0.0))?;
+    let xs = map_x.data_typed_mut::<f32>()?;
+    let ys = map_y.data_typed_mut::<f32>()?;
+    for y in 0..target_h {
+        let (_, h) = bands.iter()
+            .find(|(bottom_y, _)| (y as f64) < *bottom_y)
+            .unwrap_or(&bands[bands.len() - 1]);
+        for x in 0..target_w {
+            let (fx, fy) = (x as f64, y as f64);
+            let w = h[6] * fx + h[7] * fy + h[8];
+            let index = (y * target_w + x) as usize;
+            xs[index] = ((h[0] * fx + h[1] * fy + h[2]) / w) as f32;
+            ys[index] = ((h[3] * fx + h[4] * fy + h[5]) / w) as f32;
+        }
+    }
+
+    Ok((map_x, map_y))
+}
+
+/// 按映射表重采样单张图片
+pub fn remap_image(image: &Mat, map_x: &Mat, map_y: &Mat) -> Result<Mat> {
+    let mut remapped = Mat::default();
+    imgproc::remap(
+        image,
+        &mut remapped,
+        map_x,
+        map_y,
+        imgproc::INTER_LINEAR,
+        opencv::core::BORDER_CONSTANT,
+        Scalar::default(),
+    ).context("按映射表重采样失败")?;
+    Ok(remapped)
+}
+
+/// 按映射表重采样灰度图、二值图和闭图
+pub fn remap_processed_image(processed_image: &ProcessedImage, map_x: &Mat, map_y: &Mat) -> Result<ProcessedImage> {
+    Ok(ProcessedImage {
+        gray: remap_image(&processed_image.gray, map_x, map_y)?,
+        thresh: remap_image(&processed_image.thresh, map_x, map_y)?,
+        closed: remap_image(&processed_image.closed, map_x, map_y)?,
+    })
+}
+
+/// 合并两次透视变换，返回先做first再做second的变换矩阵
+pub fn combine_transforms(first: &Mat, second: &Mat) -> Result<Mat> {
+    let mut combined = Mat::default();
+    opencv::core::gemm(second, first, 1.0, &Mat::default(), 0.0, &mut combined, 0)
+        .with_code(ErrorCode::HomographyFailed, "合并透视变换矩阵失败")?;
+    Ok(combined)
+}
+
 /// 将四边形转换为OpenCV格式
 pub fn get_points_from_quad(quad: &Quad) -> Vector<Point2f> {
     // 将检测到的点转换为OpenCV格式
-    let points = Vector::<Point2f>::from_slice(&[
-        Point2f::new(quad.points[0].x as f32, quad.points[0].y as f32),
-        Point2f::new(quad.points[1].x as f32, quad.points[1].y as f32),
-        Point2f::new(quad.points[2].x as f32, quad.points[2].y as f32),
-        Point2f::new(quad.points[3].x as f32, quad.points[3].y as f32),
-    ]);
-    points
+    Vector::<Point2f>::from_slice(&quad.corners)
 }
 
 pub fn get_points_from_coordinate(coordinate: &Coordinate) -> Vector<Point2f> {
@@ -229,6 +352,32 @@ pub fn pers_trans_image(
     })
 }
 
+/// 对单张图片做透视变换
+pub fn warp_image(image: &Mat, transform_matrix: &Mat, target_w: i32, target_h: i32) -> Result<Mat> {
+    let mut warped = Mat::default();
+    imgproc::warp_perspective(
+        image,
+        &mut warped,
+        transform_matrix,
+        Size::new(target_w, target_h),
+        imgproc::INTER_LINEAR,
+        opencv::core::BORDER_CONSTANT,
+        opencv::core::Scalar::default(),
+    ).context("应用透视变换失败")?;
+    Ok(warped)
+}
+
+/// 截取图片中的矩形区域，超出图片的部分被裁掉
+pub fn crop_image(image: &Mat, coordinate: &Coordinate) -> Result<Mat> {
+    let x = coordinate.x.clamp(0, image.cols());
+    let y = coordinate.y.clamp(0, image.rows());
+    let w = (coordinate.x + coordinate.w).min(image.cols()) - x;
+    let h = (coordinate.y + coordinate.h).min(image.rows()) - y;
+    if w <= 0 || h <= 0 {
+        crate::bail_code!(ErrorCode::MarkJsonInvalid, "截图区域超出图片范围");
+    }
+    Ok(Mat::roi(image, opencv::core::Rect::new(x, y, w, h))?.clone_pointee())
+}
 
 /// 计算积分图
 pub fn integral_image(image: &Mat) -> Result<Mat> {
@@ -265,3 +414,110 @@ pub fn merge_coordinates(coordinates: &Vec<Coordinate>, extend_size: i32) -> Coo
     Coordinate { x, y, w, h }   
 }
 
+
+/// 将图片编码为指定格式的字节数据，ext如".jpg"、".png"
+pub fn encode_image(image: &Mat, ext: &str, params: &Vector<i32>) -> Result<Vec<u8>> {
+    let mut buffer = Vector::<u8>::new();
+    let ok = opencv::imgcodecs::imencode(ext, image, &mut buffer, params)
+        .context(format!("图片编码失败: {}", ext))?;
+    if !ok {
+        anyhow::bail!("图片编码失败: {}", ext);
+    }
+    Ok(buffer.to_vec())
+}
+
+/// 根据截图配置生成编码参数
+pub fn encode_params(config: &CropConfig) -> Vector<i32> {
+    match config.format.as_str() {
+        ".jpg" | ".jpeg" => Vector::from_slice(&[opencv::imgcodecs::IMWRITE_JPEG_QUALITY, config.quality]),
+        ".webp" => Vector::from_slice(&[opencv::imgcodecs::IMWRITE_WEBP_QUALITY, config.quality]),
+        _ => Vector::new(),
+    }
+}
+
+/// 编码截图并转为base64
+pub fn export_crops(images: &[(String, Mat)], config: &CropConfig) -> Result<Vec<RegionCrop>> {
+    let params = encode_params(config);
+    images.iter().map(|(name, image)| {
+        let bytes = encode_image(image, &config.format, &params)?;
+        Ok(RegionCrop {
+            name: name.clone(),
+            format: config.format.clone(),
+            data: general_purpose::STANDARD.encode(&bytes),
+        })
+    }).collect()
+}
+
+/// 按调试选项导出调试图片：返回base64数据和/或写入指定目录
+pub fn export_debug_images(images: &[(String, Mat)], options: &DebugOptions) -> Result<Vec<DebugImage>> {
+    let params = Vector::<i32>::new();
+    let mut debug_images = Vec::new();
+    for (name, image) in images {
+        let bytes = encode_image(image, &options.format, &params)?;
+
+        let path = match &options.output_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir).context("创建调试目录失败")?;
+                let path = std::path::Path::new(dir).join(format!("{}{}", name, options.format));
+                std::fs::write(&path, &bytes).context("保存调试图片失败")?;
+                Some(path.to_string_lossy().into_owned())
+            }
+            None => None,
+        };
+
+        let data = if options.return_images {
+            Some(general_purpose::STANDARD.encode(&bytes))
+        } else {
+            None
+        };
+
+        debug_images.push(DebugImage {
+            name: name.clone(),
+            data,
+            path,
+        });
+    }
+    Ok(debug_images)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 左右两列辅助定位点，左列中心x=12，右列中心x=182，纵向中心依次为ys
+    fn assists(ys: &[i32]) -> AssistLocation {
+        let column = |x: i32| ys.iter().map(|&y| Coordinate { x, y: y - 2, w: 4, h: 4 }).collect();
+        AssistLocation { left: column(10), right: column(180) }
+    }
+
+    #[test]
+    fn test_residual_inliers() {
+        // 第三对左侧点偏差远超其他点，整对剔除
+        let (residual, inliers) = residual_inliers(&[0.5, 1.0, 20.0, 0.5, 1.0, 1.0], 3);
+        assert_eq!(inliers, vec![0, 1]);
+        assert!((residual - 0.625_f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_piecewise_maps() -> Result<()> {
+        let target = assists(&[20, 100, 180]);
+        let mut src = target.clone();
+        src.left[1].x += 30;
+
+        // 剔除误检的中间一对后，映射与恒等变换一致
+        let (map_x, map_y) = piecewise_maps(&src, &target, &[0, 2], 200, 200)?;
+        for &(x, y) in &[(12, 20), (100, 60), (182, 180), (50, 199)] {
+            assert!((*map_x.at_2d::<f32>(y, x)? - x as f32).abs() < 1e-3);
+            assert!((*map_y.at_2d::<f32>(y, x)? - y as f32).abs() < 1e-3);
+        }
+
+        // 使用全部定位点对时，中间一对附近按其偏移映射
+        let (map_x, _) = piecewise_maps(&src, &target, &[0, 1, 2], 200, 200)?;
+        assert!((*map_x.at_2d::<f32>(100, 12)? - 42.0).abs() < 1e-3);
+
+        // 少于两对时无法分段
+        let err = piecewise_maps(&src, &target, &[0], 200, 200).unwrap_err();
+        assert_eq!(crate::error::error_code(&err), ErrorCode::HomographyFailed);
+        Ok(())
+    }
+}
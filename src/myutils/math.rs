@@ -75,4 +75,116 @@ pub fn otsu_threshold(values: &[f64]) -> (f64, f64) {
     }
     
     (best_threshold, max_variance)
-}
\ No newline at end of file
+}
+
+/// 三类Otsu阈值，返回两个分割线(t1, t2)，t1 <= t2
+/// 输入: values - 数据值向量，值应在0-1之间
+pub fn otsu_two_thresholds(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    // 三类搜索的复杂度为bins的平方，使用较粗的bins
+    const NUM_BINS: usize = 100;
+    let mut histogram = [0usize; NUM_BINS];
+    for &value in values {
+        let clamped_value = value.max(0.0).min(1.0);
+        let bin_index = ((clamped_value * (NUM_BINS - 1) as f64) as usize).min(NUM_BINS - 1);
+        histogram[bin_index] += 1;
+    }
+
+    // 前缀和，便于计算任意区间的像素数和矩
+    let mut cumulative_histogram = [0usize; NUM_BINS + 1];
+    let mut cumulative_moments = [0.0f64; NUM_BINS + 1];
+    for i in 0..NUM_BINS {
+        cumulative_histogram[i + 1] = cumulative_histogram[i] + histogram[i];
+        cumulative_moments[i + 1] = cumulative_moments[i] + (i as f64) * histogram[i] as f64;
+    }
+    let total_pixels = cumulative_histogram[NUM_BINS] as f64;
+    let total_mean = cumulative_moments[NUM_BINS] / total_pixels;
+
+    // 区间[start, end)的类间方差贡献
+    let class_variance = |start: usize, end: usize| -> Option<f64> {
+        let pixels = (cumulative_histogram[end] - cumulative_histogram[start]) as f64;
+        if pixels == 0.0 {
+            return None;
+        }
+        let mean = (cumulative_moments[end] - cumulative_moments[start]) / pixels;
+        Some(pixels * (mean - total_mean).powi(2))
+    };
+
+    let mut max_variance = 0.0;
+    let mut best = (0, 0);
+    for i in 1..NUM_BINS - 1 {
+        for j in i + 1..NUM_BINS {
+            let variance = match (class_variance(0, i), class_variance(i, j), class_variance(j, NUM_BINS)) {
+                (Some(a), Some(b), Some(c)) => a + b + c,
+                _ => continue,
+            };
+            if variance > max_variance {
+                max_variance = variance;
+                best = (i, j);
+            }
+        }
+    }
+
+    // 分割线取所在bin的下边界
+    let to_value = |bin: usize| bin as f64 / (NUM_BINS - 1) as f64;
+    (to_value(best.0), to_value(best.1))
+}
+
+/// 计算两点之间的距离
+pub fn distance(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+    ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt()
+}
+
+/// 使用鞋带公式计算多边形面积
+pub fn polygon_area(points: &[(f64, f64)]) -> f64 {
+    let n = points.len();
+    if n < 3 {
+        return 0.0;
+    }
+    let mut sum = 0.0;
+    for i in 0..n {
+        let (x1, y1) = points[i];
+        let (x2, y2) = points[(i + 1) % n];
+        sum += x1 * y2 - x2 * y1;
+    }
+    sum.abs() / 2.0
+}
+
+/// 计算中位数，输入为空时返回0
+pub fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[0.3, 0.1, 0.2]), 0.2);
+        assert_eq!(median(&[0.4, 0.1, 0.2, 0.3]), 0.25);
+        // NaN排在最后，不会导致panic
+        assert_eq!(median(&[0.3, f64::NAN, 0.1]), 0.3);
+    }
+
+    #[test]
+    fn test_otsu_two_thresholds() {
+        let (t1, t2) = otsu_two_thresholds(&[0.1, 0.1, 0.5, 0.5, 0.9, 0.9]);
+        assert!(0.1 < t1 && t1 <= 0.5 && 0.5 < t2 && t2 <= 0.9);
+        assert_eq!(otsu_two_thresholds(&[]), (0.0, 0.0));
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::{Result, Context};
-use opencv::{core::Mat, imgcodecs::{imdecode, IMREAD_COLOR}};
+use opencv::{core::{AlgorithmHint, Mat, MatTraitConst, CV_8UC1, CV_8UC4}, imgcodecs::{imdecode, IMREAD_COLOR}, imgproc};
 use serde_json;
-use std::{any::type_name, ffi::{c_char, CStr}};
+use std::{any::type_name, ffi::{c_char, c_void, CStr, CString}};
+use crate::error::{ErrorCode, WithCode};
+use crate::models::PixelFormat;
 
 pub fn from_json<T>(json_str: &str) -> Result<T>
 where
@@ -19,6 +21,14 @@ where
         .context(format!("{} 序列化失败", type_name::<T>()))
 }
 
+/// 序列化为JSON并转换为C字符串，调用方需通过free_string释放
+pub fn to_c_json<T>(value: &T) -> *mut c_char
+where
+    T: serde::Serialize,
+{
+    CString::new(to_json(value).unwrap()).unwrap().into_raw()
+}
+
 pub fn c_to_string(input_c: *const c_char) -> String {
     // 将 C 字符串指针转换为 Rust 字符串
     let c_str = unsafe { CStr::from_ptr(input_c) };
@@ -27,8 +37,123 @@ pub fn c_to_string(input_c: *const c_char) -> String {
 }
 
 pub fn c_to_mat(data_ptr: *const u8, data_len: usize) -> Result<Mat> {
+    if data_ptr.is_null() || data_len == 0 {
+        crate::bail_code!(ErrorCode::ImageDecodeFailed, "图像数据为空");
+    }
     let data = unsafe { std::slice::from_raw_parts(data_ptr, data_len) };
     // 直接使用切片，避免内存拷贝
-    let img = imdecode(&data, IMREAD_COLOR)?;
+    let img = imdecode(&data, IMREAD_COLOR).with_code(ErrorCode::ImageDecodeFailed, "图片解码失败")?;
+    if img.empty() {
+        crate::bail_code!(ErrorCode::ImageDecodeFailed, "图片解码失败，数据长度: {}", data_len);
+    }
     Ok(img)
 }
+
+/// 解码多帧编码后的图片数据，解码失败的帧返回空Mat
+pub fn c_to_mats(data_ptrs: *const *const u8, data_lens: *const usize, count: usize) -> Vec<Mat> {
+    if data_ptrs.is_null() || data_lens.is_null() {
+        return vec![];
+    }
+    let ptrs = unsafe { std::slice::from_raw_parts(data_ptrs, count) };
+    let lens = unsafe { std::slice::from_raw_parts(data_lens, count) };
+    ptrs.iter().zip(lens.iter())
+        .map(|(&data_ptr, &data_len)| {
+            if data_ptr.is_null() {
+                return Mat::default();
+            }
+            c_to_mat(data_ptr, data_len).unwrap_or_default()
+        })
+        .collect()
+}
+
+/// 将相机原始帧转换为BGR图片，避免额外的编解码
+/// data_len为缓冲区字节数，stride为每行字节数，传0表示无行填充；YUV格式的色度平面需紧跟Y平面，I420的色度行跨度为stride/2
+pub fn c_raw_to_mat(data_ptr: *const u8, data_len: usize, width: i32, height: i32, stride: i32, format: i32) -> Result<Mat> {
+    if data_ptr.is_null() {
+        crate::bail_code!(ErrorCode::ImageDecodeFailed, "图像数据为空");
+    }
+    let layout = raw_layout(data_len, width, height, stride, format)?;
+
+    // 直接引用调用方的内存，cvt_color会输出新的Mat
+    let raw = unsafe {
+        Mat::new_rows_cols_with_data_unsafe(layout.rows, width, layout.typ, data_ptr as *mut c_void, layout.stride as usize)
+            .with_code(ErrorCode::ImageDecodeFailed, "原始帧构建失败")?
+    };
+
+    let mut image = Mat::default();
+    imgproc::cvt_color(&raw, &mut image, layout.code, 0, AlgorithmHint::ALGO_HINT_DEFAULT)
+        .with_code(ErrorCode::ImageDecodeFailed, "原始帧颜色转换失败")?;
+    Ok(image)
+}
+
+/// 原始帧的内存布局
+#[derive(Debug, PartialEq, Eq)]
+struct RawLayout {
+    /// Mat的行数，YUV格式包含色度平面
+    rows: i32,
+    typ: i32,
+    /// 每行字节数
+    stride: i32,
+    /// 转换为BGR的颜色转换代码
+    code: i32,
+}
+
+/// 校验原始帧参数并计算内存布局，要求缓冲区至少有stride*rows字节，所有乘法都检查溢出
+fn raw_layout(data_len: usize, width: i32, height: i32, stride: i32, format: i32) -> Result<RawLayout> {
+    if width <= 0 || height <= 0 {
+        crate::bail_code!(ErrorCode::InvalidInput, "图像尺寸无效: {}x{}", width, height);
+    }
+
+    let format = PixelFormat::try_from(format).with_code(ErrorCode::InvalidInput, "像素格式无效")?;
+    if matches!(format, PixelFormat::Nv21 | PixelFormat::Yuv420) && (width % 2 != 0 || height % 2 != 0) {
+        crate::bail_code!(ErrorCode::InvalidInput, "YUV图像宽高必须为偶数: {}x{}", width, height);
+    }
+    let (rows, typ, row_bytes, code) = match format {
+        PixelFormat::Nv21 => (height.checked_mul(3).map(|rows| rows / 2), CV_8UC1, Some(width), imgproc::COLOR_YUV2BGR_NV21),
+        PixelFormat::Yuv420 => (height.checked_mul(3).map(|rows| rows / 2), CV_8UC1, Some(width), imgproc::COLOR_YUV2BGR_I420),
+        PixelFormat::Bgra => (Some(height), CV_8UC4, width.checked_mul(4), imgproc::COLOR_BGRA2BGR),
+        PixelFormat::Rgba => (Some(height), CV_8UC4, width.checked_mul(4), imgproc::COLOR_RGBA2BGR),
+    };
+    let (Some(rows), Some(row_bytes)) = (rows, row_bytes) else {
+        crate::bail_code!(ErrorCode::InvalidInput, "图像尺寸过大: {}x{}", width, height);
+    };
+
+    let stride = if stride == 0 { row_bytes } else { stride };
+    if stride < row_bytes {
+        crate::bail_code!(ErrorCode::InvalidInput, "行跨度{}小于每行字节数{}", stride, row_bytes);
+    }
+
+    match (stride as usize).checked_mul(rows as usize) {
+        Some(required) if data_len >= required => Ok(RawLayout { rows, typ, stride, code }),
+        Some(required) => crate::bail_code!(ErrorCode::InvalidInput, "数据长度{}小于所需的{}字节", data_len, required),
+        None => crate::bail_code!(ErrorCode::InvalidInput, "图像尺寸过大: {}x{}", width, height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::error_code;
+
+    #[test]
+    fn test_raw_frame_checks() -> Result<()> {
+        fn invalid<T>(result: Result<T>) -> bool {
+            result.is_err_and(|e| error_code(&e) == ErrorCode::InvalidInput)
+        }
+        let bgra = PixelFormat::Bgra as i32;
+        let nv21 = PixelFormat::Nv21 as i32;
+
+        // 2x2的BGRA帧，每行带4字节填充
+        let data = [128u8; 12 * 2];
+        let image = c_raw_to_mat(data.as_ptr(), data.len(), 2, 2, 12, bgra)?;
+        assert_eq!((image.cols(), image.rows()), (2, 2));
+        assert_eq!(raw_layout(6 * 3, 4, 2, 0, nv21)?.rows, 3);
+
+        assert!(invalid(c_raw_to_mat(data.as_ptr(), data.len(), 2, 2, 4, bgra)));
+        assert!(invalid(c_raw_to_mat(data.as_ptr(), data.len() - 1, 2, 2, 12, bgra)));
+        assert!(invalid(raw_layout(usize::MAX, i32::MAX, 2, 0, bgra)));
+        assert!(invalid(raw_layout(usize::MAX, 2, i32::MAX - 1, 0, nv21)));
+        assert!(invalid(raw_layout(usize::MAX, 3, 2, 0, nv21)));
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@ use crate::models::AssistLocation;
 use crate::models::ProcessedImage;
 use crate::myutils::image::merge_coordinates;
 use crate::config::AssistLocationConfig;
+use crate::error::ErrorCode;
 use anyhow::Result;
 use opencv::core::Mat;
 use opencv::core::MatTraitConst;
@@ -11,25 +12,27 @@ use opencv::{
     imgproc::{contour_area, find_contours, bounding_rect, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE},
 };
 
-pub struct AssistLocationModule;
+pub struct AssistLocationModule {
+    config: AssistLocationConfig,
+}
 
 impl AssistLocationModule {
-    pub fn new() -> Self {
-        Self
+    pub fn new(config: AssistLocationConfig) -> Self {
+        Self { config }
     }
 
     pub fn infer(&self, processed_image: &ProcessedImage, assist_location: &AssistLocation) -> Result<AssistLocation> {
-        let left_area = merge_coordinates(&assist_location.left, AssistLocationConfig::ASSIST_AREA_EXTEND_SIZE);
-        let right_area = merge_coordinates(&assist_location.right, AssistLocationConfig::ASSIST_AREA_EXTEND_SIZE);
-        let left_src_assist = Self::find_assist_location(&processed_image.closed, &left_area)?;
-        let right_src_assist = Self::find_assist_location(&processed_image.closed, &right_area)?;
+        let left_area = merge_coordinates(&assist_location.left, self.config.assist_area_extend_size);
+        let right_area = merge_coordinates(&assist_location.right, self.config.assist_area_extend_size);
+        let left_src_assist = Self::find_assist_location(&processed_image.closed, &left_area, &self.config)?;
+        let right_src_assist = Self::find_assist_location(&processed_image.closed, &right_area, &self.config)?;
         
         if left_src_assist.len() != right_src_assist.len() {
-            anyhow::bail!("辅助定位点数量不匹配，左侧找到{}个，右侧找到{}个", left_src_assist.len(), right_src_assist.len());
+            crate::bail_code!(ErrorCode::AssistMarkCountMismatch, "辅助定位点数量不匹配，左侧找到{}个，右侧找到{}个", left_src_assist.len(), right_src_assist.len());
         }
 
         if left_src_assist.len() != assist_location.left.len() {
-            anyhow::bail!("辅助定位点数量异常");
+            crate::bail_code!(ErrorCode::AssistMarkCountMismatch, "辅助定位点数量异常，期望{}个，找到{}个", assist_location.left.len(), left_src_assist.len());
         }
 
         Ok(
@@ -46,7 +49,7 @@ impl AssistLocationModule {
     // }
 
     /// 在闭图上寻找辅助定位点
-    pub fn find_assist_location(closed: &Mat, coordinate: &Coordinate) -> Result<Vec<Coordinate>> {
+    pub fn find_assist_location(closed: &Mat, coordinate: &Coordinate, config: &AssistLocationConfig) -> Result<Vec<Coordinate>> {
         // 创建感兴趣区域ROI
         let roi_rect = Rect::new(
             coordinate.x.max(0),
@@ -83,13 +86,13 @@ impl AssistLocationModule {
             let width = bounding_rect.width;
             let height = bounding_rect.height;
             
-            if width < AssistLocationConfig::ASSIST_POINT_MIN_SIZE {continue;}
-            if width > AssistLocationConfig::ASSIST_POINT_MAX_SIZE {continue;}
-            if height < AssistLocationConfig::ASSIST_POINT_MIN_SIZE {continue;}
-            if height > AssistLocationConfig::ASSIST_POINT_MAX_SIZE {continue;}
-            if (width - height).abs() > AssistLocationConfig::ASSIST_POINT_WHDIFF_MAX {continue;}
-            if area < AssistLocationConfig::ASSIST_POINT_MIN_AREA {continue;}
-            if area > AssistLocationConfig::ASSIST_POINT_MAX_AREA {continue;}
+            if width < config.assist_point_min_size {continue;}
+            if width > config.assist_point_max_size {continue;}
+            if height < config.assist_point_min_size {continue;}
+            if height > config.assist_point_max_size {continue;}
+            if (width - height).abs() > config.assist_point_whdiff_max {continue;}
+            if area < config.assist_point_min_area {continue;}
+            if area > config.assist_point_max_area {continue;}
             let fill_rate = crate::recognize::fill::calculate_fill_rate(
                 &integral_image,
                 &Coordinate {
@@ -99,7 +102,7 @@ impl AssistLocationModule {
                     h: bounding_rect.height-2,
                 }
             )?;
-            if fill_rate < AssistLocationConfig::ASSIST_POINT_MIN_FILL_RATIO {continue;}
+            if fill_rate < config.assist_point_min_fill_ratio {continue;}
 
             assist_points.push(Coordinate {
                 x: bounding_rect.x + coordinate.x,
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use opencv::core::{Mat, MatTraitConst, Point2f, Rect, Vector, CV_32FC2};
+use opencv::objdetect::{BarcodeDetector, BarcodeDetectorTraitConst, GraphicalCodeDetectorTraitConst, QRCodeDetector};
+use crate::config::CodeConfig;
+use crate::models::{AlignedSheet, CodeRegion, CodeResult, CodeType, Coordinate};
+
+#[derive(Default)]
+pub struct CodeModule;
+
+impl CodeModule {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// 识别所有条码区域：先在摆正后的区域内识别，失败的区域再到未摆正的原图上整体识别
+    /// scale为原图到对齐时缩放后图片的比例，用于把原图上识别到的条码位置映射到模板空间
+    pub fn infer(&self, aligned: &AlignedSheet, image: &Mat, scale: f64, code_regions: &[CodeRegion]) -> Result<Vec<CodeResult>> {
+        // 1. 在模板空间的区域内识别
+        let mut code_results = code_regions.iter().map(|code_region| {
+            CodeResult {
+                name: code_region.name.clone(),
+                code_type: code_region.code_type,
+                text: Self::decode_region(&aligned.warped.gray, code_region).ok().flatten(),
+                from_full_image: false,
+            }
+        }).collect::<Vec<CodeResult>>();
+
+        // 2. 区域内识别失败时，在原图上识别同类型的所有条码
+        for code_type in [CodeType::Qr, CodeType::Barcode] {
+            if !code_results.iter().any(|code_result| code_result.code_type == code_type && code_result.text.is_none()) {
+                continue;
+            }
+
+            // 跳过区域内已经识别到的内容
+            let decoded = Self::decode_all(image, code_type).unwrap_or_default();
+            let mut remaining = decoded.into_iter()
+                .filter(|(text, _)| !code_results.iter().any(|code_result| code_result.text.as_ref() == Some(text)))
+                .map(|(text, center)| {
+                    let center = center.and_then(|center| Self::to_template(center, scale, &aligned.transform).ok());
+                    (text, center)
+                })
+                .collect::<Vec<(String, Option<Point2f>)>>();
+
+            // 按条码中心在模板空间中落入的区域分配
+            for (code_result, code_region) in code_results.iter_mut().zip(code_regions.iter()) {
+                if code_result.code_type != code_type || code_result.text.is_some() {
+                    continue;
+                }
+                let found = remaining.iter().position(|(_, center)| {
+                    center.is_some_and(|center| Self::contains(&code_region.coordinate, center))
+                });
+                if let Some(index) = found {
+                    code_result.text = Some(remaining.swap_remove(index).0);
+                    code_result.from_full_image = true;
+                }
+            }
+
+            // 位置无法对应时，只在该类型仅剩一个未识别区域且仅剩一个条码时分配，避免多个区域的内容互换
+            let mut unresolved = code_results.iter_mut()
+                .filter(|code_result| code_result.code_type == code_type && code_result.text.is_none());
+            if let (Some(code_result), None, [(text, _)]) = (unresolved.next(), unresolved.next(), remaining.as_slice()) {
+                code_result.text = Some(text.clone());
+                code_result.from_full_image = true;
+            }
+        }
+
+        Ok(code_results)
+    }
+
+    /// 把原图上的点映射到模板空间
+    fn to_template(point: Point2f, scale: f64, transform: &Mat) -> Result<Point2f> {
+        let scaled = Vector::<Point2f>::from_slice(&[Point2f::new(point.x * scale as f32, point.y * scale as f32)]);
+        let mut projected = Vector::<Point2f>::new();
+        opencv::core::perspective_transform(&scaled, &mut projected, transform)?;
+        Ok(projected.get(0)?)
+    }
+
+    /// 点是否落在区域内，区域四周放宽ROI_PADDING
+    fn contains(coordinate: &Coordinate, point: Point2f) -> bool {
+        let padding = CodeConfig::ROI_PADDING as f32;
+        let (x, y) = (coordinate.x as f32, coordinate.y as f32);
+        (x - padding..=x + coordinate.w as f32 + padding).contains(&point.x)
+            && (y - padding..=y + coordinate.h as f32 + padding).contains(&point.y)
+    }
+
+    /// 在模板空间中截取条码区域并识别，区域四周留出空白便于检测
+    pub fn decode_region(warped: &Mat, code_region: &CodeRegion) -> Result<Option<String>> {
+        let coordinate = &code_region.coordinate;
+        let padding = CodeConfig::ROI_PADDING;
+        let x = (coordinate.x - padding).max(0);
+        let y = (coordinate.y - padding).max(0);
+        let w = (coordinate.x + coordinate.w + padding).min(warped.cols()) - x;
+        let h = (coordinate.y + coordinate.h + padding).min(warped.rows()) - y;
+        if w <= 0 || h <= 0 {
+            return Ok(None);
+        }
+        let roi = Mat::roi(warped, Rect::new(x, y, w, h))?.clone_pointee();
+
+        let texts = match code_region.code_type {
+            CodeType::Qr => {
+                let bytes = QRCodeDetector::default()?.detect_and_decode_def(&roi)?;
+                vec![String::from_utf8_lossy(&bytes).into_owned()]
+            }
+            CodeType::Barcode => Self::decode_barcodes(&roi)?,
+        };
+
+        Ok(texts.into_iter().find(|text| !text.is_empty()))
+    }
+
+    /// 在整张图上识别指定类型的所有条码，返回内容和条码中心，顶点数与条码数不符时中心为空
+    fn decode_all(image: &Mat, code_type: CodeType) -> Result<Vec<(String, Option<Point2f>)>> {
+        let mut decoded_info = Vector::<String>::new();
+        let mut points = Mat::default();
+        match code_type {
+            CodeType::Qr => {
+                let mut straight_code = Mat::default();
+                QRCodeDetector::default()?.detect_and_decode_multi(image, &mut decoded_info, &mut points, &mut straight_code)?;
+            }
+            CodeType::Barcode => {
+                let mut decoded_type = Vector::<String>::new();
+                BarcodeDetector::default()?.detect_and_decode_with_type(image, &mut decoded_info, &mut decoded_type, &mut points)?;
+            }
+        }
+
+        // 每个条码4个顶点
+        let corners = if points.typ() == CV_32FC2 && points.total() == decoded_info.len() * 4 {
+            points.data_typed::<Point2f>()?.to_vec()
+        } else {
+            vec![]
+        };
+        let centers = corners.chunks_exact(4).map(|quad| {
+            let x = quad.iter().map(|point| point.x).sum::<f32>() / 4.0;
+            let y = quad.iter().map(|point| point.y).sum::<f32>() / 4.0;
+            Some(Point2f::new(x, y))
+        }).chain(std::iter::repeat(None));
+
+        Ok(decoded_info.to_vec().into_iter().zip(centers).filter(|(text, _)| !text.is_empty()).collect())
+    }
+
+    fn decode_barcodes(image: &Mat) -> Result<Vec<String>> {
+        let mut decoded_info = Vector::<String>::new();
+        let mut decoded_type = Vector::<String>::new();
+        BarcodeDetector::default()?.detect_and_decode_with_type_def(image, &mut decoded_info, &mut decoded_type)?;
+        Ok(decoded_info.to_vec())
+    }
+}
@@ -1,11 +1,18 @@
-use anyhow::{Context, Result};
-use opencv::core::Mat;
-use crate::models::{Mark, MobileOutput};
-use crate::myutils::image::{get_perspective_transform_matrix_with_boundary, get_perspective_transform_matrix_with_assists, pers_trans_image, process_image};
+use anyhow::Result;
+use opencv::core::{AlgorithmHint, Mat, MatTraitConst};
+use opencv::imgproc;
+use crate::config::{ConfigOverride, FusionConfig, OrientationConfig, PreviewConfig, RecConfig, TemplateConfig};
+use crate::error::{error_code, ErrorCode, WithCode};
+use crate::models::{AlignedSheet, AssistLocation, CodeResult, Corner, DebugOptions, DewarpMaps, FillItem, InferenceOptions, KeyCaptureOutput, Mark, MobileOutput, PreviewOutput, ProcessedImage, Quad, RegionCrop, RejectedFrame};
+use crate::myutils::image::{assist_residuals, combine_transforms, crop_image, export_crops, export_debug_images, get_perspective_transform_matrix_with_boundary, get_perspective_transform_matrix_with_assists, integral_image, pers_trans_image, piecewise_maps, process_image, remap_image, remap_processed_image, residual_inliers, resize_image, warp_image};
+use crate::myutils::rendering::{render_output, render_quad, Colors, RenderMode};
+use crate::myutils::math::median;
 use crate::myutils::myjson::from_json;
-use crate::recognize::fill::RecFillModule;
+use crate::recognize::fill::{calculate_fill_rate, RecFillModule};
 use crate::recognize::location::LocationModule;
 use crate::recognize::assist_location::AssistLocationModule;
+use crate::recognize::code::CodeModule;
+use crate::recognize::score::ScoreModule;
 
 /// 识别引擎
 pub struct RecEngine {
@@ -15,93 +22,607 @@ pub struct RecEngine {
     rec_fill_module: RecFillModule,
     /// 辅助定位模块
     assist_location_module: AssistLocationModule,
+    /// 条码识别模块
+    code_module: CodeModule,
+    /// 判分模块
+    score_module: ScoreModule,
     /// 初始化mark信息
     mark: Mark,
+    /// 识别参数
+    config: RecConfig,
 }
 
 impl RecEngine {
     pub fn new(mobile_input: &String) -> Result<Self> {
+        Self::with_config(mobile_input, None)
+    }
+
+    /// 使用指定识别参数创建引擎
+    /// 参数优先级：传入的config中写出的字段 > Mark中的config字段 > 默认值
+    pub fn with_config(mobile_input: &String, config: Option<ConfigOverride>) -> Result<Self> {
+        let mark: Mark = from_json(mobile_input).with_code(ErrorCode::MarkJsonInvalid, "Mark解析失败")?;
+        Self::from_mark(mark, config)
+    }
+
+    /// 使用已解析的Mark创建引擎，参数优先级同with_config
+    pub fn from_mark(mark: Mark, config: Option<ConfigOverride>) -> Result<Self> {
+        mark.validate()?;
+        if let Some(answer_key) = &mark.answer_key {
+            answer_key.validate(&mark)?;
+        }
+        let mark_config = mark.config.clone().unwrap_or_default();
+        let config = match &config {
+            Some(config) => config.apply(&mark_config)?,
+            None => mark_config,
+        };
+        config.validate()?;
+
         Ok(Self {
-            location_module: LocationModule::new(),
-            assist_location_module: AssistLocationModule::new(),
-            rec_fill_module: RecFillModule::new(),
-            mark: from_json(mobile_input)?,
+            location_module: LocationModule::new(config.image_processing.clone()),
+            assist_location_module: AssistLocationModule::new(config.assist_location.clone()),
+            rec_fill_module: RecFillModule::new(config.fill.clone()),
+            code_module: CodeModule::new(),
+            score_module: ScoreModule::new(),
+            mark,
+            config,
+        })
+    }
+
+    /// 预览检查：只做预处理和外框定位（可选辅助定位点检查），用于实时拍摄引导
+    pub fn preview(&self, image: &Mat, check_assist: bool) -> Result<PreviewOutput> {
+        // 1. 处理图片
+        let processed_image = process_image(&image, &self.config.image_processing)?;
+
+        // 2. 定位检测，校验未通过时不报错，作为拍摄建议返回
+        let location = self.location_module.find(&processed_image, self.mark.locator)?;
+        let rejection = self.location_module.validate(&location, &processed_image.closed, self.expected_aspect()).err();
+
+        // 3. 宽高比吻合度，横向拍摄时按旋转90度比较
+        let aspect_score = self.aspect_score(&location).max(self.aspect_score(&location.rotated(1)));
+
+        // 4. 面积占比和到画面边缘的距离
+        let img_w = processed_image.gray.cols() as f64;
+        let img_h = processed_image.gray.rows() as f64;
+        let area_ratio = location.area() / (img_w * img_h);
+        let margin = location.float_points().iter()
+            .map(|&(x, y)| x.min(y).min(img_w - x).min(img_h - y))
+            .fold(f64::INFINITY, f64::min);
+        let area_score = (area_ratio / PreviewConfig::FULL_AREA_RATIO).min(1.0);
+        let margin_score = (margin / img_w / PreviewConfig::MIN_BORDER_MARGIN).clamp(0.0, 1.0);
+
+        // 5. 辅助定位点检查
+        let assist_ok = if check_assist {
+            let pers_trans_matrix = get_perspective_transform_matrix_with_boundary(&location, &self.mark.boundary)?;
+            let baizheng = pers_trans_image(
+                &processed_image, &pers_trans_matrix, self.mark.boundary.x+self.mark.boundary.w, self.mark.boundary.y+self.mark.boundary.h
+            )?;
+            Some(self.assist_location_module.infer(&baizheng, &self.mark.assist_location).is_ok())
+        } else {
+            None
+        };
+
+        let mut readiness = aspect_score * area_score * margin_score;
+        if assist_ok == Some(false) {
+            readiness *= PreviewConfig::ASSIST_FAIL_PENALTY;
+        }
+        if rejection.is_some() {
+            readiness = 0.0;
+        }
+
+        // 6. 顶点还原到原图坐标
+        let scale = image.cols() as f64 / self.config.image_processing.target_width as f64;
+        let corners = location.float_points().iter()
+            .map(|&(x, y)| Corner { x: x * scale, y: y * scale })
+            .collect();
+
+        Ok(PreviewOutput {
+            code: 0,
+            message: "success".to_string(),
+            corners,
+            aspect_score,
+            area_ratio,
+            assist_ok,
+            readiness,
+            ready: readiness >= PreviewConfig::READY_SCORE,
+            guidance_code: rejection.as_ref().map_or(0, |e| error_code(e).into()),
+            guidance: rejection.map(|e| e.to_string()),
+        })
+    }
+
+    /// 模板外框的宽高比
+    fn expected_aspect(&self) -> f64 {
+        self.mark.boundary.w as f64 / self.mark.boundary.h.max(1) as f64
+    }
+
+    /// 检测到的外框与Mark.boundary宽高比的吻合度，0-1
+    pub fn aspect_score(&self, location: &Quad) -> f64 {
+        let (quad_w, quad_h) = location.size();
+        let quad_ratio = quad_w / quad_h.max(1.0);
+        let mark_ratio = self.mark.boundary.w as f64 / self.mark.boundary.h.max(1) as f64;
+        quad_ratio.min(mark_ratio) / quad_ratio.max(mark_ratio)
+    }
+
+    /// 评估图片与本模板的匹配程度，返回匹配分数和对齐结果
+    /// 分数是外框宽高比、辅助定位点偏差和选项区域墨迹三项吻合度的乘积，
+    /// 尺寸相同的模板靠后两项区分；
+    /// 模板声明了印刷的模板ID时，识别到的ID一致则直接满分，不一致则为0；
+    /// 模板声明了页码标识时，标识处没有实心黑块则为0
+    pub fn match_template(&self, image: &Mat) -> Result<(f64, AlignedSheet)> {
+        let aligned = self.align(image)?;
+        let integral = integral_image(&aligned.warped.thresh)?;
+
+        if let Some(page_indicator) = &self.mark.page_indicator {
+            let fill_rate = calculate_fill_rate(&integral, page_indicator).unwrap_or(0.0);
+            if fill_rate < TemplateConfig::PAGE_INDICATOR_MIN_FILL_RATE {
+                return Ok((0.0, aligned));
+            }
+        }
+
+        let assist_fit = (1.0 - aligned.assist_residual / TemplateConfig::MAX_ASSIST_RESIDUAL).clamp(0.0, 1.0);
+        let layout_fit = if self.mark.rec_items.iter().all(|rec_item| rec_item.sub_options.is_empty()) {
+            1.0
+        } else {
+            (self.layout_score(&integral) / TemplateConfig::LAYOUT_FULL_INK).min(1.0)
+        };
+        let mut score = self.aspect_score(&aligned.location) * assist_fit * layout_fit;
+
+        if let (Some(template_id), Some(id_region)) = (&self.mark.template_id, &self.mark.template_id_region) {
+            match CodeModule::decode_region(&aligned.warped.gray, id_region).ok().flatten() {
+                Some(text) if &text == template_id => score = 1.0,
+                Some(_) => score = 0.0,
+                None => {}
+            }
+        }
+
+        Ok((score, aligned))
+    }
+
+    /// 定位并对齐到模板空间
+    pub fn align(&self, image: &Mat) -> Result<AlignedSheet> {
+        // 1. 处理图片
+        let processed_image = process_image(&image, &self.config.image_processing)?;
+        
+        // 2. 定位检测
+        let location = self.location_module.locate(&processed_image, self.mark.locator, self.expected_aspect())?;
+
+        // 3-5. 判断方向，完成第一次变换并找到辅助定位点
+        let (quarter_turns, location, boundary_matrix, baizheng, assist_location) = self.orient(&processed_image, &location)?;
+
+        // 6. 获取变换矩阵
+        let assists_matrix = get_perspective_transform_matrix_with_assists(&assist_location, &self.mark.assist_location)?;
+        
+        // 7. 第二次变换，辅助定位点偏差过大说明纸张弯曲，改用分段映射
+        let target_w = self.mark.boundary.x+self.mark.boundary.w;
+        let target_h = self.mark.boundary.y+self.mark.boundary.h;
+        let residuals = assist_residuals(&assist_location, &self.mark.assist_location, &assists_matrix)?;
+        let (residual, inliers) = residual_inliers(&residuals, self.mark.assist_location.left.len());
+        let (warped, dewarp) = if residual > self.config.assist_location.piecewise_residual && inliers.len() >= 2 {
+            let (map_x, map_y) = piecewise_maps(&assist_location, &self.mark.assist_location, &inliers, target_w, target_h)?;
+            let warped = remap_processed_image(&baizheng, &map_x, &map_y)?;
+            (warped, Some(DewarpMaps { boundary_transform: boundary_matrix.clone(), map_x, map_y }))
+        } else {
+            (pers_trans_image(&baizheng, &assists_matrix, target_w, target_h)?, None)
+        };
+
+        Ok(AlignedSheet {
+            location,
+            warped,
+            transform: combine_transforms(&boundary_matrix, &assists_matrix)?,
+            orientation: quarter_turns as i32 * 90,
+            assist_residual: residual,
+            dewarp,
         })
     }
 
+    /// 判断答题卡方向：先按0度做第一次变换，能找到辅助定位点（声明了方向标记时还要求标记处为实心黑块）即直接采用，
+    /// 0度不通过时再尝试其余三个方向，通过检查的方向按方向标记的填涂率评分，没有方向标记时按选项区域的墨迹比例评分，取分数最高的方向
+    /// 返回顺时针旋转的90度个数、按答题卡方向排列的外框、变换矩阵、变换结果和辅助定位点
+    fn orient(&self, processed_image: &ProcessedImage, location: &Quad) -> Result<(usize, Quad, Mat, ProcessedImage, AssistLocation)> {
+        let mut passed = Vec::new();
+        let mut first_error = None;
+        let mut mark_found = false;
+        for quarter_turns in 0..4 {
+            let rotated = location.rotated(quarter_turns);
+            let boundary_matrix = get_perspective_transform_matrix_with_boundary(&rotated, &self.mark.boundary)?;
+            let baizheng = pers_trans_image(
+                processed_image, &boundary_matrix, self.mark.boundary.x+self.mark.boundary.w, self.mark.boundary.y+self.mark.boundary.h
+            )?;
+
+            let score = match &self.mark.orientation_mark {
+                Some(orientation_mark) => {
+                    let fill_rate = calculate_fill_rate(&integral_image(&baizheng.thresh)?, orientation_mark).unwrap_or(0.0);
+                    if fill_rate < OrientationConfig::MARK_MIN_FILL_RATE {
+                        continue;
+                    }
+                    mark_found = true;
+                    fill_rate
+                }
+                // 0度通过检查时直接采用，不需要评分
+                None if quarter_turns == 0 => 0.0,
+                None => self.layout_score(&integral_image(&baizheng.thresh)?),
+            };
+
+            match self.assist_location_module.infer(&baizheng, &self.mark.assist_location) {
+                Ok(assist_location) if quarter_turns == 0 => return Ok((0, rotated, boundary_matrix, baizheng, assist_location)),
+                Ok(assist_location) => passed.push((quarter_turns, score, (rotated, boundary_matrix, baizheng, assist_location))),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        let scores = passed.iter().map(|&(quarter_turns, score, _)| (quarter_turns, score)).collect::<Vec<(usize, f64)>>();
+        if let Some(best) = Self::pick_orientation(&scores, self.mark.orientation_mark.is_some())? {
+            let (quarter_turns, _, (rotated, boundary_matrix, baizheng, assist_location)) = passed.swap_remove(best);
+            return Ok((quarter_turns, rotated, boundary_matrix, baizheng, assist_location));
+        }
+
+        // 方向标记在任何方向都找不到时报告方向错误，否则报告0度方向的失败原因
+        match first_error {
+            Some(e) if mark_found || self.mark.orientation_mark.is_none() => Err(e),
+            _ => crate::bail_code!(ErrorCode::OrientationNotDetected, "四个方向均未找到方向标记"),
+        }
+    }
+
+    /// 从通过检查的(方向, 分数)中选出分数最高的一项，返回其下标，没有候选时返回None
+    /// 最高分与次高分差距不足时：声明了方向标记说明标记位置有歧义，返回OrientationNotDetected；
+    /// 没有方向标记时按旋转角度从小到大优先
+    fn pick_orientation(candidates: &[(usize, f64)], has_mark: bool) -> Result<Option<usize>> {
+        let mut order = (0..candidates.len()).collect::<Vec<usize>>();
+        order.sort_by(|&a, &b| candidates[b].1.total_cmp(&candidates[a].1));
+        let Some(&best) = order.first() else {
+            return Ok(None);
+        };
+        let tied = order.iter().copied()
+            .filter(|&index| candidates[best].1 - candidates[index].1 < OrientationConfig::MIN_SCORE_GAP)
+            .collect::<Vec<usize>>();
+        if tied.len() < 2 {
+            return Ok(Some(best));
+        }
+        if has_mark {
+            crate::bail_code!(
+                ErrorCode::OrientationNotDetected, "无法区分答题卡方向，{}度和{}度的方向标记均为实心",
+                candidates[tied[0]].0 * 90, candidates[tied[1]].0 * 90
+            );
+        }
+        Ok(tied.into_iter().min_by_key(|&index| candidates[index].0))
+    }
+
+    /// 版式吻合度：所有选项区域在二值图中的平均墨迹比例，方向正确时印刷的选项框落在选项区域内
+    fn layout_score(&self, integral: &Mat) -> f64 {
+        let rates = self.mark.rec_items.iter()
+            .flat_map(|rec_item| rec_item.sub_options.iter())
+            .map(|coordinate| calculate_fill_rate(integral, coordinate).unwrap_or(0.0))
+            .collect::<Vec<f64>>();
+        if rates.is_empty() {
+            return 0.0;
+        }
+        rates.iter().sum::<f64>() / rates.len() as f64
+    }
+
     pub fn inference(&self, image: &Mat) -> Result<MobileOutput> {
+        self.inference_with_options(image, &InferenceOptions::default())
+    }
+
+    pub fn inference_with_options(&self, image: &Mat, options: &InferenceOptions) -> Result<MobileOutput> {
+        let aligned = self.align(image)?;
+        self.inference_aligned(image, &aligned, options)
+    }
+
+    /// 在已对齐的图片上识别
+    pub fn inference_aligned(&self, image: &Mat, aligned: &AlignedSheet, options: &InferenceOptions) -> Result<MobileOutput> {
         // 1. 初始化输出
         let mut mobile_output = MobileOutput::new(&self.mark);
-        // 2. 处理图片
-        let processed_image = process_image(&image)?;
-        
-        // 3. 定位检测
-        let location = self.location_module.infer(&processed_image)?;
+        mobile_output.orientation = aligned.orientation;
+        // 2. 填涂识别
+        self.rec_fill_module.infer(&aligned.warped, &mut mobile_output)?;
+        // 3. 条码识别
+        if !self.mark.code_regions.is_empty() {
+            mobile_output.codes = self.decode_codes(image, aligned)?;
+        }
 
-        // 4. 获取变换矩阵
-        let pers_trans_matrix = get_perspective_transform_matrix_with_boundary(&location, &self.mark.boundary)?;
+        // 4. 区域截图
+        if !self.mark.crop_regions.is_empty() {
+            mobile_output.crops = self.crop_regions(image, aligned)?;
+        }
 
-        // 5. 第一次变换
-        let baizheng = pers_trans_image(
-            &processed_image, &pers_trans_matrix, self.mark.boundary.x+self.mark.boundary.w, self.mark.boundary.y+self.mark.boundary.h
-        )?;
+        // 5. 判分，推理选项中的标准答案优先，缺考时不判分
+        let answer_key = match &options.answer_key {
+            Some(answer_key) => {
+                answer_key.validate(&self.mark)?;
+                Some(answer_key)
+            }
+            None => self.mark.answer_key.as_ref(),
+        };
+        if let Some(answer_key) = answer_key.filter(|_| !mobile_output.absent) {
+            self.score_module.infer(&mut mobile_output, answer_key)?;
+        }
 
-        // 6. 找到辅助定位点
-        let assist_location = self.assist_location_module.infer(&baizheng, &self.mark.assist_location)?;
-        
-        // 7. 获取变换矩阵
-        let pers_trans_matrix = get_perspective_transform_matrix_with_assists(&assist_location, &self.mark.assist_location)?;
-        
-        // 8. 第二次变换
-        let baizheng = pers_trans_image(
-            &baizheng, &pers_trans_matrix, self.mark.boundary.x+self.mark.boundary.w, self.mark.boundary.y+self.mark.boundary.h
+        // 6. 调试输出，失败时不影响识别结果
+        if let Some(debug) = &options.debug {
+            let debug_images = self.render_debug_images(image, aligned, &mobile_output)
+                .and_then(|debug_images| export_debug_images(&debug_images, debug));
+            match debug_images {
+                Ok(debug_images) => mobile_output.debug_images = debug_images,
+                Err(e) => mobile_output.debug_error = Some(format!("调试图片导出失败: {:#}", e)),
+            }
+        }
+
+        Ok(mobile_output)
+    }
+
+    /// 标准答案采集：正常识别老师填涂的答案卡，以更严格的置信度要求生成标准答案
+    /// 存在不确定的题目时返回AnswerKeyAmbiguous并列出所有不确定的题目
+    pub fn capture_answer_key(&self, image: &Mat) -> Result<KeyCaptureOutput> {
+        // 1. 正常识别，不判分
+        let aligned = self.align(image)?;
+        let mut mobile_output = MobileOutput::new(&self.mark);
+        self.rec_fill_module.infer(&aligned.warped, &mut mobile_output)?;
+
+        // 2. 生成标准答案
+        let (answer_key, ambiguous) = self.score_module.capture_key(&mobile_output, self.mark.answer_key.as_ref());
+        if !ambiguous.is_empty() {
+            let mut output = KeyCaptureOutput::failed(
+                ErrorCode::AnswerKeyAmbiguous, format!("有{}道题不确定，请修改后重新扫描", ambiguous.len())
+            );
+            output.ambiguous = ambiguous;
+            return Ok(output);
+        }
+
+        Ok(KeyCaptureOutput {
+            code: ErrorCode::Success.into(),
+            message: "success".to_string(),
+            answer_key: Some(answer_key),
+            ambiguous: vec![],
+        })
+    }
+
+    /// 识别Mark中声明的条码区域，原图上整体识别到的条码按位置映射回模板空间
+    fn decode_codes(&self, image: &Mat, aligned: &AlignedSheet) -> Result<Vec<CodeResult>> {
+        let scale = self.config.image_processing.target_width as f64 / image.cols() as f64;
+        self.code_module.infer(aligned, image, scale, &self.mark.code_regions)
+    }
+
+    /// 截取Mark中声明的自由作答区域，彩色区域用合并后的变换矩阵重新变换原图
+    fn crop_regions(&self, image: &Mat, aligned: &AlignedSheet) -> Result<Vec<RegionCrop>> {
+        let color = if self.mark.crop_regions.iter().any(|crop_region| crop_region.color) {
+            let resized = resize_image(image, self.config.image_processing.target_width)?;
+            let target_w = self.mark.boundary.x+self.mark.boundary.w;
+            let target_h = self.mark.boundary.y+self.mark.boundary.h;
+            Some(match &aligned.dewarp {
+                Some(dewarp) => remap_image(
+                    &warp_image(&resized, &dewarp.boundary_transform, target_w, target_h)?, &dewarp.map_x, &dewarp.map_y
+                )?,
+                None => warp_image(&resized, &aligned.transform, target_w, target_h)?,
+            })
+        } else {
+            None
+        };
+
+        let mut crops = Vec::new();
+        for crop_region in self.mark.crop_regions.iter() {
+            let source = match &color {
+                Some(color) if crop_region.color => color,
+                _ => &aligned.warped.gray,
+            };
+            crops.push((crop_region.name.clone(), crop_image(source, &crop_region.coordinate)?));
+        }
+
+        export_crops(&crops, &self.config.crop)
+    }
+
+    /// 识别失败时尽量导出已有的中间结果：处理后的闭图，以及外框定位成功时的定位图
+    /// 用于排查外框或辅助定位点找不到的原因，结果写入识别失败的输出，导出失败时记录原因
+    pub fn attach_failure_debug_images(&self, image: &Mat, debug: &DebugOptions, mobile_output: &mut MobileOutput) {
+        match self.render_failure_images(image).and_then(|debug_images| export_debug_images(&debug_images, debug)) {
+            Ok(debug_images) => mobile_output.debug_images = debug_images,
+            Err(e) => mobile_output.debug_error = Some(format!("调试图片导出失败: {:#}", e)),
+        }
+    }
+
+    fn render_failure_images(&self, image: &Mat) -> Result<Vec<(String, Mat)>> {
+        let processed_image = process_image(image, &self.config.image_processing)?;
+        let mut debug_images = vec![("processed_closed".to_string(), processed_image.closed.clone())];
+        if let Ok(location) = self.location_module.find(&processed_image, self.mark.locator) {
+            let mut render_image = resize_image(image, self.config.image_processing.target_width)?;
+            render_quad(&mut render_image, &location, Some(RenderMode::Hollow), None, None)?;
+            debug_images.insert(0, ("debug_location".to_string(), render_image));
+        }
+        Ok(debug_images)
+    }
+
+    /// 渲染中间结果：外框定位、摆正后的灰度图/二值图/闭图、识别结果
+    fn render_debug_images(&self, image: &Mat, aligned: &AlignedSheet, mobile_output: &MobileOutput) -> Result<Vec<(String, Mat)>> {
+        let mut render_image = resize_image(image, self.config.image_processing.target_width)?;
+        render_quad(
+            &mut render_image, &aligned.location, Some(RenderMode::Hollow), None, None
         )?;
-        // 9. 填涂识别
-        self.rec_fill_module.infer(&baizheng, &mut mobile_output)?;
 
+        // 将灰度图转换为RGB格式
+        let mut render_out = Mat::default();
+        imgproc::cvt_color(&aligned.warped.gray, &mut render_out, imgproc::COLOR_GRAY2BGR, 0, AlgorithmHint::ALGO_HINT_DEFAULT)?;
+        render_output(&mut render_out, mobile_output, &self.mark.assist_location, Some(RenderMode::Hollow), Some(Colors::orange()), Some(2), Some(2.0))?;
 
-        // 渲染
-        #[cfg(debug_assertions)]
-        {
-            use opencv::{core::{AlgorithmHint, Vector}, imgcodecs::imwrite, imgproc};
+        Ok(vec![
+            ("debug_location".to_string(), render_image),
+            ("baizheng_gray".to_string(), aligned.warped.gray.clone()),
+            ("baizheng_thresh".to_string(), aligned.warped.thresh.clone()),
+            ("baizheng_closed".to_string(), aligned.warped.closed.clone()),
+            ("render_out".to_string(), render_out),
+        ])
+    }
 
-            use crate::{config::ImageProcessingConfig, myutils::{image::resize_image, rendering::{render_assist_location, render_output, render_quad, Colors, RenderMode}}};
+    /// 多帧融合识别：每帧独立对齐并计算填涂率，取中位数后统一做阈值判定
+    pub fn inference_multi(&self, images: &[Mat]) -> Result<MobileOutput> {
+        // 1. 逐帧对齐并计算填涂率
+        let mut frames = Vec::new();
+        let mut rejected_frames = Vec::new();
+        for (index, image) in images.iter().enumerate() {
+            if image.empty() {
+                rejected_frames.push(RejectedFrame {
+                    index,
+                    code: ErrorCode::ImageDecodeFailed.into(),
+                    reason: "图片为空或解码失败".to_string(),
+                });
+                continue;
+            }
+            let measured = self.align(image).and_then(|aligned| {
+                let mut frame_output = MobileOutput::new(&self.mark);
+                frame_output.orientation = aligned.orientation;
+                self.rec_fill_module.measure(&aligned.warped, &mut frame_output)?;
+                if !self.mark.code_regions.is_empty() {
+                    frame_output.codes = self.decode_codes(image, &aligned)?;
+                }
+                if !self.mark.crop_regions.is_empty() {
+                    frame_output.crops = self.crop_regions(image, &aligned)?;
+                }
+                Ok(frame_output)
+            });
+            match measured {
+                Ok(frame_output) => frames.push((index, frame_output)),
+                Err(e) => rejected_frames.push(RejectedFrame { index, code: error_code(&e).into(), reason: e.to_string() }),
+            }
+        }
 
-            let mut render_image = resize_image(image, ImageProcessingConfig::TARGET_WIDTH)?;
-            let _ = render_quad(
-                &mut render_image, &location, Some(RenderMode::Hollow), None, None
-            )?;
-            let debug_path = format!("dev/test_data/debug/{}.jpg", "debug_location");
-            let params = Vector::<i32>::new();
-            imwrite(&debug_path, &render_image, &params)
-                .context("保存调试图片失败")?;
-
-            let gray_path = format!("dev/test_data/debug/{}.jpg", "baizheng_gray");
-            imwrite(&gray_path, &baizheng.gray, &params)
-                .context("保存调试图片失败")?;
-            
-            let thresh_path = format!("dev/test_data/debug/{}.jpg", "baizheng_thresh");
-            imwrite(&thresh_path, &baizheng.thresh, &params)
-                .context("保存调试图片失败")?;
-            
-            let close_path = format!("dev/test_data/debug/{}.jpg", "baizheng_closed");
-            imwrite(&close_path, &baizheng.closed, &params)
-                .context("保存调试图片失败")?;
-
-            let mut render_out = baizheng.gray.clone();
-            // 将灰度图转换为RGB格式
-            let mut rgb_image = Mat::default();
-            imgproc::cvt_color(&render_out, &mut rgb_image, imgproc::COLOR_GRAY2BGR, 0, AlgorithmHint::ALGO_HINT_DEFAULT)?;
-            render_out = rgb_image;
-            let _ = render_output(&mut render_out, &mobile_output, &self.mark.assist_location,Some(RenderMode::Hollow), Some(Colors::orange()), Some(2), Some(2.0));
-
-            let render_out_path = format!("dev/test_data/debug/{}.jpg", "render_out");
-            imwrite(&render_out_path, &render_out, &params)
-                .context("保存调试图片失败")?;
-            
+        if frames.is_empty() {
+            // 所有帧都失败时，使用第一帧的错误码
+            let code = rejected_frames.first()
+                .and_then(|frame| ErrorCode::try_from(frame.code).ok())
+                .unwrap_or(ErrorCode::ImageDecodeFailed);
+            crate::bail_code!(code, "所有帧均无法对齐，共{}帧", images.len());
+        }
+
+        // 2. 剔除填涂率与中位数偏差过大的帧
+        if frames.len() >= FusionConfig::MIN_FRAMES_FOR_OUTLIER {
+            frames = Self::reject_outliers(frames, &mut rejected_frames);
         }
 
+        // 3. 融合填涂率和灰度深浅，坐标取第一帧的优化结果
+        let fused = Self::fuse_fill_values(&frames, |fill_item| fill_item.fill_rate);
+        let fused_darkness = Self::fuse_fill_values(&frames, |fill_item| fill_item.darkness);
+        let mut mobile_output = frames[0].1.clone();
+        for (i, rec_result) in mobile_output.rec_results.iter_mut().enumerate() {
+            for (j, fill_item) in rec_result.fill_items.iter_mut().enumerate() {
+                fill_item.fill_rate = fused[i][j];
+                fill_item.darkness = fused_darkness[i][j];
+            }
+        }
+        for (i, flag) in mobile_output.flags.iter_mut().enumerate() {
+            let rates = frames.iter().map(|(_, frame_output)| frame_output.flags[i].fill_rate).collect::<Vec<f64>>();
+            flag.fill_rate = median(&rates);
+        }
+        // 条码取第一个识别成功的帧
+        for (i, code_result) in mobile_output.codes.iter_mut().enumerate() {
+            if let Some((_, frame_output)) = frames.iter().find(|(_, frame_output)| frame_output.codes[i].text.is_some()) {
+                *code_result = frame_output.codes[i].clone();
+            }
+        }
+
+        // 4. 阈值判定
+        self.rec_fill_module.decide(&mut mobile_output)?;
+        if let Some(answer_key) = self.mark.answer_key.as_ref().filter(|_| !mobile_output.absent) {
+            self.score_module.infer(&mut mobile_output, answer_key)?;
+        }
+
+        rejected_frames.sort_by_key(|frame| frame.index);
+        mobile_output.used_frames = frames.iter().map(|(index, _)| *index).collect();
+        mobile_output.rejected_frames = rejected_frames;
 
         Ok(mobile_output)
     }
-}
\ No newline at end of file
+
+    /// 剔除填涂率与中位数偏差过大的帧，所有帧都偏差过大时保留偏差最小的一帧
+    fn reject_outliers(frames: Vec<(usize, MobileOutput)>, rejected_frames: &mut Vec<RejectedFrame>) -> Vec<(usize, MobileOutput)> {
+        let fused = Self::fuse_fill_values(&frames, |fill_item| fill_item.fill_rate);
+        let mut kept = Vec::new();
+        let mut outliers = Vec::new();
+        for (index, frame_output) in frames {
+            let deviation = Self::mean_deviation(&frame_output, &fused);
+            if deviation > FusionConfig::MAX_MEAN_DEVIATION {
+                outliers.push((deviation, index, frame_output));
+            } else {
+                kept.push((index, frame_output));
+            }
+        }
+
+        if kept.is_empty() {
+            let best = outliers.iter().enumerate()
+                .min_by(|a, b| a.1.0.total_cmp(&b.1.0))
+                .map(|(i, _)| i);
+            if let Some(best) = best {
+                let (_, index, frame_output) = outliers.remove(best);
+                kept.push((index, frame_output));
+            }
+        }
+        for (deviation, index, _) in outliers {
+            rejected_frames.push(RejectedFrame {
+                index,
+                code: ErrorCode::FrameOutlier.into(),
+                reason: format!("填涂率与其他帧差异过大，平均偏差{:.3}", deviation),
+            });
+        }
+        kept
+    }
+
+    /// 逐选项取各帧指定数值的中位数
+    fn fuse_fill_values(frames: &[(usize, MobileOutput)], value: impl Fn(&FillItem) -> f64) -> Vec<Vec<f64>> {
+        let first = &frames[0].1;
+        first.rec_results.iter().enumerate().map(|(i, rec_result)| {
+            (0..rec_result.fill_items.len()).map(|j| {
+                let rates = frames.iter()
+                    .map(|(_, frame_output)| value(&frame_output.rec_results[i].fill_items[j]))
+                    .collect::<Vec<f64>>();
+                median(&rates)
+            }).collect()
+        }).collect()
+    }
+
+    /// 单帧填涂率与融合结果的平均绝对偏差
+    fn mean_deviation(frame_output: &MobileOutput, fused: &[Vec<f64>]) -> f64 {
+        let deviations = frame_output.rec_results.iter().zip(fused.iter())
+            .flat_map(|(rec_result, fused_rates)| {
+                rec_result.fill_items.iter().zip(fused_rates.iter())
+                    .map(|(fill_item, fill_rate)| (fill_item.fill_rate - fill_rate).abs())
+            })
+            .collect::<Vec<f64>>();
+        if deviations.is_empty() {
+            return 0.0;
+        }
+        deviations.iter().sum::<f64>() / deviations.len() as f64
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reject_outliers_keeps_best_frame() {
+        let mark = Mark::for_test(&[(1, 2)]);
+        let frames = [[0.0, 0.5], [0.5, 1.0], [1.0, 0.0]].iter().enumerate().map(|(index, rates)| {
+            let mut output = MobileOutput::new(&mark);
+            output.set_fill_rates(&[rates]);
+            (index, output)
+        }).collect();
+
+        // 三帧的平均偏差分别为0.25、0.25、0.5，都超过阈值，保留偏差最小的第一帧
+        let mut rejected_frames = Vec::new();
+        let kept = RecEngine::reject_outliers(frames, &mut rejected_frames);
+        assert_eq!(kept.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![0]);
+        assert_eq!(rejected_frames.iter().map(|frame| frame.index).collect::<Vec<usize>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_pick_orientation() -> Result<()> {
+        assert_eq!(RecEngine::pick_orientation(&[], false)?, None);
+        assert_eq!(RecEngine::pick_orientation(&[(1, 0.3)], true)?, Some(0));
+        assert_eq!(RecEngine::pick_orientation(&[(1, 0.1), (2, 0.3)], false)?, Some(1));
+
+        // 没有方向标记时分数接近的方向按角度从小到大优先
+        assert_eq!(RecEngine::pick_orientation(&[(3, 0.3), (1, 0.29)], false)?, Some(1));
+
+        // 多个方向的方向标记都是实心，说明标记位置有歧义
+        let err = RecEngine::pick_orientation(&[(1, 0.9), (3, 0.9)], true).unwrap_err();
+        assert_eq!(error_code(&err), ErrorCode::OrientationNotDetected);
+        Ok(())
+    }
+}
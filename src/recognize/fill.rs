@@ -1,29 +1,58 @@
 use anyhow::{Ok, Result};
 use opencv::core::{Mat, MatTraitConst};
 use crate::config::FillConfig;
-use crate::models::{Coordinate, MobileOutput, ProcessedImage, RecType};
+use crate::models::{ChoicePolicy, Coordinate, FlagKind, GridPosition, Judgment, MarkState, MobileOutput, ProcessedImage, RecStatus, RecType};
 use crate::models::FillItem;
 
-pub struct RecFillModule;
+pub struct RecFillModule {
+    config: FillConfig,
+}
 
 impl RecFillModule {
 
-    pub fn new() -> Self {
-        Self
+    pub fn new(config: FillConfig) -> Self {
+        Self { config }
     }
 
     pub fn infer(&self, process_image: &ProcessedImage, mobile_output: &mut MobileOutput) -> Result<()> {
+        // 1. 计算所有选项的填涂率
+        self.measure(process_image, mobile_output)?;
+
+        // 2. 阈值判定
+        self.decide(mobile_output)?;
+
+        Ok(())
+        
+    }
+
+    /// 计算所有选项的填涂率，不做阈值判定
+    pub fn measure(&self, process_image: &ProcessedImage, mobile_output: &mut MobileOutput) -> Result<()> {
         // 1. 计算积分图
         let integral_image = crate::myutils::image::integral_image(&process_image.thresh)?;
 
-        // 2. 计算所有选项的填涂率和otsu值
+        // 2. 优化坐标并计算所有选项的填涂率
         self.refine_all_fill_coordinate(&integral_image, mobile_output)?;
         self.calculate_all_fill_rate(&integral_image, mobile_output)?;
+        for flag in mobile_output.flags.iter_mut() {
+            flag.fill_rate = calculate_fill_rate(&integral_image, &flag.coordinate)?;
+        }
+
+        // 3. 擦除识别模式下，额外计算灰度图上的深浅
+        if self.config.erasure_aware {
+            let gray_integral = crate::myutils::image::integral_image(&process_image.gray)?;
+            self.calculate_all_darkness(&gray_integral, mobile_output)?;
+        }
+
+        Ok(())
+    }
+
+    /// 根据所有选项填涂率的otsu值判定选中状态，状态标记不参与阈值计算
+    pub fn decide(&self, mobile_output: &mut MobileOutput) -> Result<()> {
         let fill_rates = mobile_output.rec_results.iter()
             .flat_map(|rec_result| rec_result.fill_items.iter().map(|item| item.fill_rate))
             .collect::<Vec<f64>>();
         let (mut thresh, _) = crate::myutils::math::otsu_threshold(&fill_rates);
-        thresh = thresh.min(FillConfig::FILL_RATE_MIN);
+        thresh = thresh.min(self.config.fill_rate_min);
         thresh = (thresh * 100.0).round() / 100.0;
 
         #[cfg(debug_assertions)]
@@ -31,15 +60,119 @@ impl RecFillModule {
             println!("填涂率阈值: {:.4}", thresh);
         }
 
-        // // 3. 单选识别
-        // self.set_single_fill(mobile_output, thresh)?;
-        // // 4. 多选识别
-        // self.set_multi_fill(mobile_output, thresh)?;
-        self.set_default_fill(mobile_output, thresh)?;
-        
+        // 2. 区分填涂、擦除和未填涂
+        self.classify_marks(mobile_output, thresh)?;
+
+        // 3. 主选项策略识别
+        self.set_single_fill(mobile_output)?;
+        // 4. 阈值策略识别
+        self.set_default_fill(mobile_output)?;
+        // 数字网格识别
+        self.set_grid_fill(mobile_output)?;
+        // 判断题结果
+        self.set_judgment(mobile_output)?;
+
+        // 5. 置信度和标记
+        mobile_output.fill_threshold = thresh;
+        self.set_confidence(mobile_output, thresh)?;
+
+        // 6. 状态标记，缺考时清空作答结果
+        self.set_status_flags(mobile_output, thresh)?;
+        if mobile_output.absent {
+            mobile_output.suppress_answers();
+        }
+
+        Ok(())
+    }
+
+    /// 用选项的阈值判定状态标记是否被填涂，并汇总到顶层状态
+    pub fn set_status_flags(&self, mobile_output: &mut MobileOutput, thresh: f64) -> Result<()> {
+        for flag in mobile_output.flags.iter_mut() {
+            flag.marked = flag.fill_rate > thresh;
+        }
+        mobile_output.absent = mobile_output.flags.iter().any(|flag| flag.marked && flag.kind == FlagKind::Absent);
+        mobile_output.disciplinary = mobile_output.flags.iter().any(|flag| flag.marked && flag.kind == FlagKind::Disciplinary);
+
+        Ok(())
+    }
+
+    /// 根据填涂率到阈值的距离计算选项置信度，并结合前两名选项的差距计算题目置信度
+    pub fn set_confidence(&self, mobile_output: &mut MobileOutput, thresh: f64) -> Result<()> {
+        let margin = self.config.confidence_margin;
+        for rec_result in mobile_output.rec_results.iter_mut() {
+            for fill_item in rec_result.fill_items.iter_mut() {
+                fill_item.confidence = ((fill_item.fill_rate - thresh).abs() / margin).min(1.0);
+            }
+
+            let selected = rec_result.rec_result.iter().filter(|&&selected| selected).count();
+            rec_result.blank = selected == 0;
+            rec_result.multiple_marked_on_single = rec_result.rec_tpye.single_answer() && selected > 1;
+            rec_result.borderline = rec_result.fill_items.iter()
+                .any(|item| (item.fill_rate - thresh).abs() < self.config.borderline_margin);
+
+            let mut confidence = rec_result.fill_items.iter()
+                .map(|item| item.confidence)
+                .fold(1.0, f64::min);
+
+            // 单选题还要看最高的两个选项是否拉开差距
+            if rec_result.rec_tpye.single_answer() && selected > 0 {
+                let mut fill_rates = rec_result.fill_items.iter().map(|item| item.fill_rate).collect::<Vec<f64>>();
+                fill_rates.sort_by(|a, b| b.total_cmp(a));
+                if fill_rates.len() > 1 {
+                    let gap_confidence = ((fill_rates[0] - fill_rates[1]) / margin).min(1.0);
+                    confidence = confidence.min(gap_confidence);
+                }
+            }
+            rec_result.confidence = confidence;
+        }
+
+        Ok(())
+    }
+
+    /// 根据二值填涂率判定填涂状态，擦除识别模式下再结合灰度深浅找出擦除痕迹
+    /// 擦除痕迹在二值图上可能超过阈值，但在灰度图上明显比真正的填涂浅
+    pub fn classify_marks(&self, mobile_output: &mut MobileOutput, thresh: f64) -> Result<()> {
+        for rec_result in mobile_output.rec_results.iter_mut() {
+            for fill_item in rec_result.fill_items.iter_mut() {
+                fill_item.state = if fill_item.fill_rate > thresh { MarkState::Filled } else { MarkState::Empty };
+            }
+        }
+
+        if !self.config.erasure_aware {
+            return Ok(());
+        }
+
+        // 按灰度深浅把所有选项分为空白、擦除、填涂三类
+        let darkness = mobile_output.rec_results.iter()
+            .flat_map(|rec_result| rec_result.fill_items.iter().map(|item| item.darkness))
+            .collect::<Vec<f64>>();
+        let (erased_thresh, filled_thresh) = crate::myutils::math::otsu_two_thresholds(&darkness);
+
+        // 三类划分总会得到中间类，中间类必须与填涂类和空白类都拉开差距才是擦除痕迹
+        // 否则只是把填涂类或空白类按深浅波动拆成了两半
+        let mean = |values: Vec<f64>| if values.is_empty() { None } else { Some(values.iter().sum::<f64>() / values.len() as f64) };
+        let empty_mean = mean(darkness.iter().copied().filter(|&d| d < erased_thresh).collect());
+        let erased_mean = mean(darkness.iter().copied().filter(|&d| d >= erased_thresh && d < filled_thresh).collect());
+        let filled_mean = mean(darkness.iter().copied().filter(|&d| d >= filled_thresh).collect());
+        match (empty_mean, erased_mean, filled_mean) {
+            (Some(empty_mean), Some(erased_mean), Some(filled_mean))
+                if filled_mean - erased_mean >= self.config.erased_min_gap
+                    && erased_mean - empty_mean >= self.config.erased_min_contrast => {}
+            _ => return Ok(()),
+        }
+
+        for rec_result in mobile_output.rec_results.iter_mut() {
+            for fill_item in rec_result.fill_items.iter_mut() {
+                if fill_item.darkness >= filled_thresh {
+                    continue;
+                }
+                if fill_item.state == MarkState::Filled || fill_item.darkness >= erased_thresh {
+                    fill_item.state = MarkState::Erased;
+                }
+            }
+        }
 
         Ok(())
-        
     }
 
     pub fn set_multi_fill(&self, mobile_output: &mut MobileOutput, thresh: f64) -> Result<()> {
@@ -60,52 +193,160 @@ impl RecFillModule {
         Ok(())
     }
 
-    pub fn set_default_fill(&self, mobile_output: &mut MobileOutput, thresh: f64) -> Result<()> {
+    pub fn set_default_fill(&self, mobile_output: &mut MobileOutput) -> Result<()> {
         for rec_result in mobile_output.rec_results.iter_mut() {
-            if rec_result.rec_tpye != RecType::MultipleChoice && rec_result.rec_tpye != RecType::SingleChoice{
+            if rec_result.rec_tpye == RecType::NumericGrid {
+                continue;
+            }
+            if rec_result.policy != ChoicePolicy::Threshold {
                 continue;
             }
             let fill_items = &mut rec_result.fill_items;
             for (index,fill_item) in fill_items.iter_mut().enumerate() {
-                if fill_item.fill_rate > thresh {
-                    rec_result.rec_result[index] = true;
-                } else {
-                    rec_result.rec_result[index] = false;
-                }
+                rec_result.rec_result[index] = fill_item.state == MarkState::Filled;
             }
+
+            let selected = rec_result.rec_result.iter().filter(|&&selected| selected).count();
+            rec_result.status = match selected {
+                0 => RecStatus::Blank,
+                1 => RecStatus::Answered,
+                _ if rec_result.rec_tpye.single_answer() => RecStatus::MultipleAnswers,
+                _ => RecStatus::Answered,
+            };
         }
         
         Ok(())
     }
 
-    pub fn set_single_fill(&self, mobile_output: &mut MobileOutput, thresh: f64) -> Result<()> {
+    /// 主选项策略：超过阈值的选项中，最高者领先第二名足够多时只选最高者，
+    /// 否则将这些选项都标记为选中并报告多选
+    pub fn set_single_fill(&self, mobile_output: &mut MobileOutput) -> Result<()> {
         for rec_result in mobile_output.rec_results.iter_mut() {
-            if rec_result.rec_tpye != RecType::SingleChoice {
+            if rec_result.rec_tpye == RecType::NumericGrid {
                 continue;
             }
-            
-            // 找到填涂率最高的选项
-            let mut max_fill_rate = 0.0;
-            let mut max_index = None;
-            
-            for (index, fill_item) in rec_result.fill_items.iter().enumerate() {
-                if fill_item.fill_rate > max_fill_rate {
-                    max_fill_rate = fill_item.fill_rate;
-                    max_index = Some(index);
-                }
+            if rec_result.policy != ChoicePolicy::Dominant {
+                continue;
             }
-            
-            // 如果找到了最大填涂率且大于阈值，则标记为选中
-            if let Some(index) = max_index {
-                if max_fill_rate > thresh {
-                    rec_result.rec_result[index] = true;
+
+            let (selected, status) = self.pick_dominant(&rec_result.fill_items);
+            rec_result.rec_result = selected;
+            rec_result.status = status;
+        }
+        
+        Ok(())
+    }
+
+    /// 判断题：根据两个选项的选中状态给出对、错、未作答或冲突
+    pub fn set_judgment(&self, mobile_output: &mut MobileOutput) -> Result<()> {
+        for rec_result in mobile_output.rec_results.iter_mut() {
+            if rec_result.rec_tpye != RecType::Judgment {
+                continue;
+            }
+            rec_result.judgment = Some(match rec_result.rec_result.as_slice() {
+                [true, false] => Judgment::True,
+                [false, true] => Judgment::False,
+                [false, false] => Judgment::Blank,
+                _ => Judgment::Conflict,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// 数字网格：逐列按判定策略识别，拼出数字串并报告每一位的状态
+    pub fn set_grid_fill(&self, mobile_output: &mut MobileOutput) -> Result<()> {
+        for rec_result in mobile_output.rec_results.iter_mut() {
+            if rec_result.rec_tpye != RecType::NumericGrid {
+                continue;
+            }
+            let Some(grid) = rec_result.grid else {
+                continue;
+            };
+
+            let rows = grid.rows();
+            let mut text = String::new();
+            let mut positions = Vec::with_capacity(grid.columns);
+            for column in 0..grid.columns {
+                let range = column * rows..(column + 1) * rows;
+                let fill_items = &rec_result.fill_items[range.clone()];
+                let (selected, status) = match rec_result.policy {
+                    ChoicePolicy::Dominant => self.pick_dominant(fill_items),
+                    ChoicePolicy::Threshold => {
+                        let selected = fill_items.iter()
+                            .map(|fill_item| fill_item.state == MarkState::Filled)
+                            .collect::<Vec<bool>>();
+                        let status = match selected.iter().filter(|&&selected| selected).count() {
+                            0 => RecStatus::Blank,
+                            1 => RecStatus::Answered,
+                            _ => RecStatus::MultipleAnswers,
+                        };
+                        (selected, status)
+                    }
+                };
+
+                // 行号即数字，第11行是空白选项
+                let digit = match status {
+                    RecStatus::Answered => selected.iter().position(|&selected| selected)
+                        .filter(|&row| row < 10)
+                        .map(|row| row as u8),
+                    _ => None,
+                };
+                match (status, digit) {
+                    (RecStatus::Answered, Some(digit)) => text.push(char::from(b'0' + digit)),
+                    (RecStatus::Answered, None) => {}
+                    _ => text.push('?'),
                 }
+
+                rec_result.rec_result[range].copy_from_slice(&selected);
+                positions.push(GridPosition { digit, status });
             }
+
+            rec_result.status = if positions.iter().any(|position| position.status == RecStatus::MultipleAnswers) {
+                RecStatus::MultipleAnswers
+            } else if positions.iter().all(|position| position.status == RecStatus::Blank) {
+                RecStatus::Blank
+            } else {
+                RecStatus::Answered
+            };
+            rec_result.text = Some(text);
+            rec_result.positions = positions;
         }
-        
+
         Ok(())
     }
 
+    /// 在一组选项中挑选主选项，返回各选项的选中状态和作答状态
+    pub fn pick_dominant(&self, fill_items: &[FillItem]) -> (Vec<bool>, RecStatus) {
+        let mut selected = vec![false; fill_items.len()];
+
+        // 判定为填涂的候选项，按填涂率从高到低排序
+        let mut candidates = fill_items.iter().enumerate()
+            .filter(|(_, fill_item)| fill_item.state == MarkState::Filled)
+            .map(|(index, fill_item)| (index, fill_item.fill_rate))
+            .collect::<Vec<(usize, f64)>>();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        match candidates.len() {
+            0 => (selected, RecStatus::Blank),
+            1 => {
+                selected[candidates[0].0] = true;
+                (selected, RecStatus::Answered)
+            }
+            _ => {
+                if candidates[0].1 - candidates[1].1 >= self.config.dominant_gap {
+                    selected[candidates[0].0] = true;
+                    (selected, RecStatus::Answered)
+                } else {
+                    for (index, _) in candidates {
+                        selected[index] = true;
+                    }
+                    (selected, RecStatus::MultipleAnswers)
+                }
+            }
+        }
+    }
+
     pub fn calculate_all_fill_rate(&self, integral_image: &Mat, mobile_output: &mut MobileOutput) -> Result<()> {
         for rec_result in mobile_output.rec_results.iter_mut() {
             let fill_items = &mut rec_result.fill_items;
@@ -118,6 +359,18 @@ impl RecFillModule {
         Ok(())
     }
 
+    /// 用灰度积分图计算选项区域的平均深浅，0为纯白，1为纯黑
+    pub fn calculate_all_darkness(&self, gray_integral: &Mat, mobile_output: &mut MobileOutput) -> Result<()> {
+        for rec_result in mobile_output.rec_results.iter_mut() {
+            for fill_item in rec_result.fill_items.iter_mut() {
+                let brightness = calculate_fill_rate(gray_integral, &fill_item.coordinate)?;
+                fill_item.darkness = 1.0 - brightness;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn refine_all_fill_coordinate(&self, integral_image: &Mat, mobile_output: &mut MobileOutput) -> Result<()> {
         for rec_result in mobile_output.rec_results.iter_mut() {
             let res = self.refine_items_fill_coordinate(integral_image, &mut rec_result.fill_items);
@@ -140,8 +393,8 @@ impl RecFillModule {
         let mut best_coordinates: Vec<Coordinate> = Vec::new();
 
         // 在-2到2的范围内搜索最优坐标偏移
-        for dx in -FillConfig::REFINE_COOR_RANGE ..= FillConfig::REFINE_COOR_RANGE{
-            for dy in -FillConfig::REFINE_COOR_RANGE ..= FillConfig::REFINE_COOR_RANGE {
+        for dx in -self.config.refine_coor_range ..= self.config.refine_coor_range{
+            for dy in -self.config.refine_coor_range ..= self.config.refine_coor_range {
                 let mut fill_rates = Vec::new();
                 let mut temp_coordinates = Vec::new();
                 
@@ -239,4 +492,164 @@ pub fn calculate_fill_rate(integral_image: &Mat, coordinate: &Coordinate) -> Res
     let fill_rate = white_pixels / area;
     
     Ok(fill_rate)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::error::{error_code, ErrorCode};
+    use crate::models::{GridSpec, Mark, RecResult};
+
+    #[test]
+    fn test_confidence_with_nan() -> Result<()> {
+        let mark = Mark::for_test(&[(1, 3)]);
+        let mut output = MobileOutput::new(&mark);
+        output.set_fill_rates(&[&[0.9, f64::NAN, 0.1]]);
+        output.rec_results[0].rec_result[0] = true;
+
+        // NaN填涂率不能导致panic
+        let fill_module = RecFillModule::new(FillConfig::default());
+        fill_module.set_confidence(&mut output, 0.45)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_choice_policy() -> Result<()> {
+        let mark = Mark::for_test(&[(1, 4), (4, 2)]);
+        let mut output = MobileOutput::new(&mark);
+        assert!(output.rec_results.iter().all(|rec_result| rec_result.policy == ChoicePolicy::Threshold));
+
+        // 主选项策略：领先足够多时只选最高者，否则报告多选
+        let fill_module = RecFillModule::new(FillConfig::default());
+        output.set_fill_rates(&[&[0.9, 0.5, 0.1, 0.1]]);
+        let fill_items = &mut output.rec_results[0].fill_items;
+        fill_items[0].state = MarkState::Filled;
+        fill_items[1].state = MarkState::Filled;
+        assert_eq!(fill_module.pick_dominant(fill_items), (vec![true, false, false, false], RecStatus::Answered));
+        fill_items[1].fill_rate = 0.88;
+        assert_eq!(fill_module.pick_dominant(fill_items), (vec![true, true, false, false], RecStatus::MultipleAnswers));
+
+        // 阈值策略下单选题多涂仍被报告
+        fill_module.set_default_fill(&mut output)?;
+        fill_module.set_confidence(&mut output, 0.5)?;
+        assert_eq!(output.rec_results[0].status, RecStatus::MultipleAnswers);
+        assert!(output.rec_results[0].multiple_marked_on_single);
+        Ok(())
+    }
+
+    #[test]
+    fn test_classify_erased_marks() -> Result<()> {
+        let mark = Mark::for_test(&[(1, 4), (1, 4), (1, 4)]);
+        let fill_module = RecFillModule::new(FillConfig { erasure_aware: true, ..Default::default() });
+        let classify = |darkness: [f64; 12]| -> Result<Vec<MarkState>> {
+            let mut output = MobileOutput::new(&mark);
+            for (fill_item, &darkness) in output.rec_results.iter_mut().flat_map(|rec_result| rec_result.fill_items.iter_mut()).zip(darkness.iter()) {
+                fill_item.darkness = darkness;
+                fill_item.fill_rate = darkness;
+            }
+            fill_module.classify_marks(&mut output, 0.45)?;
+            Ok(output.rec_results.iter().flat_map(|rec_result| rec_result.fill_items.iter().map(|item| item.state)).collect())
+        };
+
+        // 没有擦除的卡面：空白选项的深浅波动不能被当作擦除
+        let states = classify([0.62, 0.03, 0.05, 0.04, 0.60, 0.09, 0.10, 0.04, 0.64, 0.03, 0.05, 0.09])?;
+        assert!(!states.contains(&MarkState::Erased));
+        assert_eq!(states.iter().filter(|&&state| state == MarkState::Filled).count(), 3);
+
+        // 深浅介于空白和填涂之间的选项为擦除
+        let states = classify([0.62, 0.03, 0.05, 0.04, 0.60, 0.09, 0.35, 0.04, 0.64, 0.03, 0.05, 0.09])?;
+        assert_eq!(states[6], MarkState::Erased);
+        assert_eq!(states.iter().filter(|&&state| state == MarkState::Erased).count(), 1);
+        Ok(())
+    }
+
+    /// 设置填涂率后以固定阈值0.45完成判定，避免otsu阈值随测试数据漂移
+    pub(crate) fn decide_fixed(output: &mut MobileOutput, rates: &[&[f64]]) -> Result<()> {
+        let fill_module = RecFillModule::new(FillConfig::default());
+        output.set_fill_rates(rates);
+        fill_module.classify_marks(output, 0.45)?;
+        fill_module.set_single_fill(output)?;
+        fill_module.set_default_fill(output)?;
+        fill_module.set_grid_fill(output)?;
+        fill_module.set_judgment(output)?;
+        fill_module.set_confidence(output, 0.45)
+    }
+
+    /// 按下标生成填涂率，filled中的选项为0.9，其余为0.05
+    pub(crate) fn grid_rates(count: usize, filled: &[usize]) -> Vec<f64> {
+        (0..count).map(|index| if filled.contains(&index) { 0.9 } else { 0.05 }).collect()
+    }
+
+    #[test]
+    fn test_numeric_grid() -> Result<()> {
+        let mut mark = Mark::for_test(&[(3, 33), (3, 20)]);
+        mark.rec_items[0].grid = Some(GridSpec { columns: 3, blank_option: true });
+        mark.rec_items[1].grid = Some(GridSpec { columns: 2, blank_option: false });
+        mark.validate()?;
+
+        // 第一题：7、空白选项、0；第二题：第一位多涂，第二位未作答
+        let mut output = MobileOutput::new(&mark);
+        decide_fixed(&mut output, &[grid_rates(33, &[7, 21, 22]).as_slice(), grid_rates(20, &[1, 2]).as_slice()])?;
+        let positions = |rec_result: &RecResult| rec_result.positions.iter().map(|position| (position.digit, position.status)).collect::<Vec<_>>();
+
+        assert_eq!(output.rec_results[0].text.as_deref(), Some("70"));
+        assert_eq!(output.rec_results[0].status, RecStatus::Answered);
+        assert_eq!(positions(&output.rec_results[0]), vec![
+            (Some(7), RecStatus::Answered),
+            (None, RecStatus::Answered),
+            (Some(0), RecStatus::Answered),
+        ]);
+
+        assert_eq!(output.rec_results[1].text.as_deref(), Some("??"));
+        assert_eq!(output.rec_results[1].status, RecStatus::MultipleAnswers);
+        assert_eq!(positions(&output.rec_results[1]), vec![(None, RecStatus::MultipleAnswers), (None, RecStatus::Blank)]);
+
+        // 选项数与网格不符
+        mark.rec_items[1].grid = Some(GridSpec { columns: 3, blank_option: false });
+        assert_eq!(error_code(&mark.validate().unwrap_err()), ErrorCode::MarkJsonInvalid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_judgment() -> Result<()> {
+        let mark = Mark::for_test(&[(4, 2), (4, 2), (4, 2), (4, 2)]);
+        let mut output = MobileOutput::new(&mark);
+        decide_fixed(&mut output, &[&[0.9, 0.05], &[0.05, 0.9], &[0.05, 0.05], &[0.9, 0.88]])?;
+
+        let judgments = output.rec_results.iter().map(|rec_result| rec_result.judgment).collect::<Vec<_>>();
+        assert_eq!(judgments, vec![
+            Some(Judgment::True),
+            Some(Judgment::False),
+            Some(Judgment::Blank),
+            Some(Judgment::Conflict),
+        ]);
+        // 阈值策略下两个都涂报告为多选
+        assert_eq!(output.rec_results[3].status, RecStatus::MultipleAnswers);
+        assert!(output.rec_results[3].multiple_marked_on_single);
+        Ok(())
+    }
+
+    #[test]
+    fn test_absent_flag() -> Result<()> {
+        let mut mark = Mark::for_test(&[(1, 2)]);
+        mark.status_flags.push(crate::myutils::myjson::from_json(
+            r#"{"name": "缺考", "kind": 1, "coordinate": {"x": 150, "y": 10, "w": 10, "h": 10}}"#
+        )?);
+        mark.validate()?;
+        let white = Mat::new_rows_cols_with_default(200, 200, opencv::core::CV_8UC1, opencv::core::Scalar::all(255.0))?;
+        let processed = ProcessedImage { gray: white.clone(), thresh: white.clone(), closed: white };
+
+        let fill_module = RecFillModule::new(FillConfig::default());
+        let mut output = MobileOutput::new(&mark);
+        fill_module.measure(&processed, &mut output)?;
+        output.rec_results[0].fill_items[1].fill_rate = 0.0;
+        fill_module.decide(&mut output)?;
+        assert!(output.absent && !output.disciplinary);
+        assert!(output.rec_results.iter().all(|rec_result| rec_result.blank));
+
+        // 超出模板范围的状态标记在创建引擎时报错
+        mark.status_flags[0].coordinate.x = 300;
+        assert_eq!(error_code(&mark.validate().unwrap_err()), ErrorCode::MarkJsonInvalid);
+        Ok(())
+    }
+}
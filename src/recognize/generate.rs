@@ -0,0 +1,269 @@
+use anyhow::Result;
+use opencv::core::{Mat, MatTraitConst, Point2i, Rect, Vector};
+use opencv::imgproc;
+use crate::config::{MarkGenConfig, RecConfig};
+use crate::error::ErrorCode;
+use crate::models::{AssistLocation, Coordinate, GridSpec, LocatorStrategy, Mark, Quad, RecItem, RecType};
+use crate::myutils::image::{get_perspective_transform_matrix_with_boundary, integral_image, pers_trans_image, process_image};
+use crate::myutils::math::median;
+use crate::recognize::assist_location::AssistLocationModule;
+use crate::recognize::fill::calculate_fill_rate;
+use crate::recognize::location::LocationModule;
+
+/// 模板生成：从空白答题卡的扫描图生成Mark草稿，需人工确认后使用
+pub struct MarkGenerator {
+    location_module: LocationModule,
+    config: RecConfig,
+}
+
+impl MarkGenerator {
+    pub fn new(config: RecConfig) -> Self {
+        Self {
+            location_module: LocationModule::new(config.image_processing.clone()),
+            config,
+        }
+    }
+
+    pub fn generate(&self, image: &Mat) -> Result<Mark> {
+        // 1. 处理图片并定位外框
+        let processed_image = process_image(image, &self.config.image_processing)?;
+        let location = self.location_module.infer(&processed_image)?;
+
+        // 2. 按模板宽度换算外框，模板空间以整页宽度为MarkGenConfig::TEMPLATE_WIDTH
+        let scale = MarkGenConfig::TEMPLATE_WIDTH as f64 / processed_image.gray.cols() as f64;
+        let boundary = Self::boundary_from_quad(&location, scale);
+        let pers_trans_matrix = get_perspective_transform_matrix_with_boundary(&location, &boundary)?;
+        let baizheng = pers_trans_image(
+            &processed_image, &pers_trans_matrix, boundary.x+boundary.w, boundary.y+boundary.h
+        )?;
+
+        // 3. 在外框内左右两侧的条带中寻找辅助定位点
+        let strip_w = (boundary.w as f64 * MarkGenConfig::ASSIST_STRIP_RATIO) as i32;
+        let left_strip = Coordinate { x: boundary.x, y: boundary.y, w: strip_w, h: boundary.h };
+        let right_strip = Coordinate { x: boundary.x + boundary.w - strip_w, y: boundary.y, w: strip_w, h: boundary.h };
+        let left = AssistLocationModule::find_assist_location(&baizheng.closed, &left_strip, &self.config.assist_location)?;
+        let right = AssistLocationModule::find_assist_location(&baizheng.closed, &right_strip, &self.config.assist_location)?;
+        if left.is_empty() || left.len() != right.len() {
+            crate::bail_code!(ErrorCode::AssistMarkCountMismatch, "辅助定位点数量异常，左侧找到{}个，右侧找到{}个", left.len(), right.len());
+        }
+
+        // 4. 在两侧条带之间寻找选项框，并分组为识别项目
+        let inset = MarkGenConfig::FRAME_INSET;
+        let bubble_area = Coordinate {
+            x: boundary.x + strip_w,
+            y: boundary.y + inset,
+            w: boundary.w - strip_w * 2,
+            h: boundary.h - inset * 2,
+        };
+        let bubbles = Self::find_bubbles(&baizheng.closed, &bubble_area)?;
+        let rec_items = Self::group_bubbles(bubbles);
+
+        Ok(Mark {
+            boundary,
+            locator: LocatorStrategy::Frame,
+            rec_items,
+            assist_location: AssistLocation { left, right },
+            config: None,
+            code_regions: vec![],
+            crop_regions: vec![],
+            status_flags: vec![],
+            template_id: None,
+            template_id_region: None,
+            page_indicator: None,
+            orientation_mark: None,
+            answer_key: None,
+        })
+    }
+
+    /// 外框在模板空间中的位置：左上角和宽高按比例缩放
+    fn boundary_from_quad(location: &Quad, scale: f64) -> Coordinate {
+        let (w, h) = location.size();
+        let x = location.points.iter().map(|p| p.x).min().unwrap_or(0);
+        let y = location.points.iter().map(|p| p.y).min().unwrap_or(0);
+        Coordinate {
+            x: (x as f64 * scale).round() as i32,
+            y: (y as f64 * scale).round() as i32,
+            w: (w * scale).round() as i32,
+            h: (h * scale).round() as i32,
+        }
+    }
+
+    /// 寻找印刷的选项框：尺寸和宽高比在范围内、内部基本空白的轮廓，再剔除与多数选项尺寸差异大的轮廓
+    pub fn find_bubbles(closed: &Mat, area: &Coordinate) -> Result<Vec<Coordinate>> {
+        let roi_rect = Rect::new(
+            area.x.max(0),
+            area.y.max(0),
+            area.w.min(closed.cols() - area.x.max(0)),
+            area.h.min(closed.rows() - area.y.max(0))
+        );
+        let roi = Mat::roi(closed, roi_rect)?.clone_pointee();
+
+        let mut contours = Vector::<Vector<Point2i>>::new();
+        imgproc::find_contours(
+            &roi,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+            Point2i::new(0, 0),
+        )?;
+
+        let integral_image = integral_image(&roi)?;
+        let mut candidates = Vec::new();
+        for contour in contours.iter() {
+            let bounding_rect = imgproc::bounding_rect(&contour)?;
+            let width = bounding_rect.width;
+            let height = bounding_rect.height;
+            if width < MarkGenConfig::BUBBLE_MIN_SIZE || height < MarkGenConfig::BUBBLE_MIN_SIZE {continue;}
+            if width > MarkGenConfig::BUBBLE_MAX_SIZE || height > MarkGenConfig::BUBBLE_MAX_SIZE {continue;}
+            let aspect = width as f64 / height as f64;
+            if !(MarkGenConfig::BUBBLE_MIN_ASPECT..=MarkGenConfig::BUBBLE_MAX_ASPECT).contains(&aspect) {continue;}
+
+            // 空白卡上的选项框只有边框，实心块是定位点或页码标识
+            let coordinate = Coordinate { x: bounding_rect.x, y: bounding_rect.y, w: width, h: height };
+            let fill_rate = calculate_fill_rate(&integral_image, &coordinate).unwrap_or(1.0);
+            if fill_rate > MarkGenConfig::BUBBLE_MAX_FILL_RATIO {continue;}
+
+            candidates.push(Coordinate {
+                x: bounding_rect.x + roi_rect.x,
+                y: bounding_rect.y + roi_rect.y,
+                w: width,
+                h: height,
+            });
+        }
+
+        if candidates.is_empty() {
+            return Ok(candidates);
+        }
+        let median_w = median(&candidates.iter().map(|c| c.w as f64).collect::<Vec<f64>>());
+        let median_h = median(&candidates.iter().map(|c| c.h as f64).collect::<Vec<f64>>());
+        let tolerance = MarkGenConfig::BUBBLE_SIZE_TOLERANCE;
+        candidates.retain(|c| {
+            (c.w as f64 - median_w).abs() <= median_w * tolerance && (c.h as f64 - median_h).abs() <= median_h * tolerance
+        });
+
+        Ok(candidates)
+    }
+
+    /// 将选项框按行分组，行内按间距拆成题目；连续10或11行列位置一致、行距紧凑均匀的块视为数字网格
+    pub fn group_bubbles(bubbles: Vec<Coordinate>) -> Vec<RecItem> {
+        if bubbles.is_empty() {
+            return vec![];
+        }
+        let median_w = median(&bubbles.iter().map(|c| c.w as f64).collect::<Vec<f64>>());
+        let median_h = median(&bubbles.iter().map(|c| c.h as f64).collect::<Vec<f64>>());
+        let center_x = |c: &Coordinate| c.x as f64 + c.w as f64 / 2.0;
+        let center_y = |c: &Coordinate| c.y as f64 + c.h as f64 / 2.0;
+
+        // 1. 按中心y聚成行，行内按x排序
+        let mut sorted = bubbles;
+        sorted.sort_by(|a, b| center_y(a).total_cmp(&center_y(b)));
+        let mut rows: Vec<Vec<Coordinate>> = Vec::new();
+        for bubble in sorted {
+            match rows.last_mut() {
+                Some(row) if (center_y(&bubble) - center_y(&row[0])).abs() < median_h / 2.0 => row.push(bubble),
+                _ => rows.push(vec![bubble]),
+            }
+        }
+        for row in rows.iter_mut() {
+            row.sort_by_key(|c| c.x);
+        }
+
+        // 2. 行内间距明显大于选项框宽度的地方拆分为不同题目
+        let split_row = |row: &Vec<Coordinate>| -> Vec<Vec<Coordinate>> {
+            let mut groups: Vec<Vec<Coordinate>> = Vec::new();
+            for bubble in row {
+                match groups.last_mut() {
+                    Some(group) if (bubble.x - (group.last().unwrap().x + group.last().unwrap().w)) as f64 <= median_w * MarkGenConfig::ITEM_GAP_FACTOR => {
+                        group.push(bubble.clone())
+                    }
+                    _ => groups.push(vec![bubble.clone()]),
+                }
+            }
+            groups
+        };
+        let same_columns = |a: &Vec<Coordinate>, b: &Vec<Coordinate>| {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(p, q)| (center_x(p) - center_x(q)).abs() < median_w / 2.0)
+        };
+        // 数字网格的行间距紧凑且均匀，每行只有一组选项；10道纵向排列的单选题行距一般更大
+        let is_grid = |block: &[Vec<Coordinate>]| {
+            let pitches = block.windows(2)
+                .map(|pair| center_y(&pair[1][0]) - center_y(&pair[0][0]))
+                .collect::<Vec<f64>>();
+            let pitch = median(&pitches);
+            pitch <= median_h * MarkGenConfig::GRID_MAX_PITCH_FACTOR
+                && pitches.iter().all(|p| (p - pitch).abs() <= pitch * MarkGenConfig::GRID_PITCH_TOLERANCE)
+                && block.iter().all(|row| split_row(row).len() == 1)
+        };
+
+        // 3. 按行输出题目，遇到数字网格时整块输出
+        let mut rec_items = Vec::new();
+        let mut i = 0;
+        while i < rows.len() {
+            let block_len = (i + 1..rows.len()).take_while(|&j| same_columns(&rows[i], &rows[j])).count() + 1;
+            if (block_len == 10 || block_len == 11) && is_grid(&rows[i..i + block_len]) {
+                let columns = rows[i].len();
+                let sub_options = (0..columns)
+                    .flat_map(|column| rows[i..i + block_len].iter().map(move |row| row[column].clone()))
+                    .collect();
+                rec_items.push(RecItem {
+                    rec_type: RecType::NumericGrid,
+                    sub_options,
+                    policy: None,
+                    grid: Some(GridSpec { columns, blank_option: block_len == 11 }),
+                });
+                i += block_len;
+                continue;
+            }
+
+            for group in split_row(&rows[i]) {
+                rec_items.push(RecItem {
+                    rec_type: RecType::SingleChoice,
+                    sub_options: group,
+                    policy: None,
+                    grid: None,
+                });
+            }
+            i += 1;
+        }
+
+        rec_items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按行生成选项框，每行columns个，列间距15，行间距pitches依次累加
+    fn bubble_rows(columns: i32, pitches: &[i32]) -> Vec<Coordinate> {
+        let mut y = 10;
+        let mut bubbles = Vec::new();
+        for row in 0..=pitches.len() {
+            bubbles.extend((0..columns).map(|column| Coordinate { x: 100 + column * 15, y, w: 10, h: 10 }));
+            if let Some(pitch) = pitches.get(row) {
+                y += pitch;
+            }
+        }
+        bubbles
+    }
+
+    #[test]
+    fn test_group_bubbles() {
+        // 10行紧凑均匀的块是数字网格，选项按列排列
+        let rec_items = MarkGenerator::group_bubbles(bubble_rows(3, &[14; 9]));
+        assert_eq!(rec_items.len(), 1);
+        assert_eq!(rec_items[0].rec_type, RecType::NumericGrid);
+        let grid = rec_items[0].grid.unwrap();
+        assert_eq!((grid.columns, grid.blank_option), (3, false));
+        assert_eq!(rec_items[0].sub_options[1].y, 24);
+
+        // 行距较大的10道单选题
+        let rec_items = MarkGenerator::group_bubbles(bubble_rows(4, &[40; 9]));
+        assert_eq!(rec_items.len(), 10);
+        assert!(rec_items.iter().all(|rec_item| rec_item.rec_type == RecType::SingleChoice && rec_item.sub_options.len() == 4));
+
+        // 行距不均匀时不视为数字网格
+        let rec_items = MarkGenerator::group_bubbles(bubble_rows(4, &[14, 14, 14, 14, 24, 14, 14, 14, 14]));
+        assert_eq!(rec_items.len(), 10);
+    }
+}
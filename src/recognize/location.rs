@@ -1,30 +1,59 @@
 use anyhow::Result;
 use opencv::{
-    core::{Mat, Vector, Point2i},
+    core::{Mat, Vector, Point2f, Point2i, Size, TermCriteria},
     imgproc,
     prelude::*,
 };
-use crate::models::{ContourInfo, Quad, ProcessedImage};
-use crate::config::ImageProcessingConfig;
+use crate::models::{ContourInfo, LocatorStrategy, Quad, ProcessedImage};
+use crate::config::{BoundaryRefineConfig, BoundaryValidationConfig, CornerLocatorConfig, ImageProcessingConfig};
+use crate::myutils::math::distance;
+use crate::error::ErrorCode;
 
-pub struct LocationModule;
+pub struct LocationModule {
+    config: ImageProcessingConfig,
+}
 
 impl LocationModule {
 
-    pub fn new() -> Self {
-        Self
+    pub fn new(config: ImageProcessingConfig) -> Self {
+        Self { config }
     }
 
-    pub fn infer(&self, processed_image: &ProcessedImage) -> Result<Quad> {
-        let boundaries = self.detect_boundary(&processed_image.closed)?;
-        let boundary = self.filter_boundary(&boundaries, &processed_image.closed)?;
-        let valid = self.validate_boundary(&boundary);
-        if !valid {
-            anyhow::bail!("边界验证失败");
+    /// 按模板声明的定位方式定位，并做几何校验和宽高比（模板外框的宽/高）校验
+    pub fn locate(&self, processed_image: &ProcessedImage, locator: LocatorStrategy, expected_aspect: f64) -> Result<Quad> {
+        let boundary = self.find(processed_image, locator)?;
+        self.validate(&boundary, &processed_image.closed, expected_aspect)?;
+        Ok(boundary)
+    }
+
+    /// 按模板声明的定位方式定位，不做校验
+    pub fn find(&self, processed_image: &ProcessedImage, locator: LocatorStrategy) -> Result<Quad> {
+        match locator {
+            LocatorStrategy::Frame => self.detect(processed_image),
+            LocatorStrategy::CornerFiducials => self.infer_corners(processed_image),
         }
+    }
+
+    /// 几何校验和宽高比校验
+    pub fn validate(&self, boundary: &Quad, image: &Mat, expected_aspect: f64) -> Result<()> {
+        self.validate_boundary(boundary, image)?;
+        Self::validate_aspect(boundary, expected_aspect)
+    }
+
+    pub fn infer(&self, processed_image: &ProcessedImage) -> Result<Quad> {
+        let boundary = self.detect(processed_image)?;
+        self.validate_boundary(&boundary, &processed_image.closed)?;
         Ok(boundary)
     }
 
+    /// 外部黑框定位，不做校验
+    pub fn detect(&self, processed_image: &ProcessedImage) -> Result<Quad> {
+        let boundaries = self.detect_boundary(&processed_image.closed)?;
+        let best = self.select_boundary(&boundaries, &processed_image.closed)?;
+        let boundary = self.approx_quad(best)?;
+        self.refine_boundary(&boundary, &best.points, &processed_image.gray)
+    }
+
     pub fn detect_boundary(&self, morphology: &Mat) -> Result<Vec<ContourInfo>> {
         
         // 查找连通区域（外部轮廓）
@@ -33,13 +62,14 @@ impl LocationModule {
             morphology,
             &mut contours,
             imgproc::RETR_EXTERNAL,
-            imgproc::CHAIN_APPROX_SIMPLE,
+            // 保留全部轮廓点，用于边缘直线拟合
+            imgproc::CHAIN_APPROX_NONE,
             Point2i::new(0, 0),
         )?;
 
         let h = morphology.rows();
         let w = morphology.cols();
-        let min_area = ImageProcessingConfig::MIN_AREA_RATIO * (w as f64) * (h as f64);
+        let min_area = self.config.min_area_ratio * (w as f64) * (h as f64);
 
         let mut contour_infos = Vec::new();
         for i in 0..contours.len() {
@@ -61,9 +91,14 @@ impl LocationModule {
     }
 
     pub fn filter_boundary(&self, boundaries: &Vec<ContourInfo>, image: &Mat) -> Result<Quad> {
-        // 过滤边界，选一个最合适的。
+        let best = self.select_boundary(boundaries, image)?;
+        self.approx_quad(best)
+    }
+
+    /// 过滤边界，选一个最合适的轮廓
+    pub fn select_boundary<'a>(&self, boundaries: &'a [ContourInfo], image: &Mat) -> Result<&'a ContourInfo> {
         if boundaries.is_empty() {
-            anyhow::bail!("未找到合适的外部黑框");
+            crate::bail_code!(ErrorCode::NoFrameFound, "未找到合适的外部黑框");
         }
 
         let mut best_contour = None;
@@ -87,7 +122,7 @@ impl LocationModule {
             let margin = x.min(y).min(w - x - cw).min(h - y - ch).max(0); // 取最小边距，且不小于0
             
             // score = area - margin * penalty
-            let score = area - (margin as f64) * ImageProcessingConfig::MARGIN_PENALTY;
+            let score = area - (margin as f64) * self.config.margin_penalty;
             
             if score > best_score {
                 best_score = score;
@@ -95,11 +130,17 @@ impl LocationModule {
             }
         }
 
-        let best = best_contour.ok_or_else(|| anyhow::anyhow!("未找到合适的外部黑框"))?;
-        
+        match best_contour {
+            Some(best) => Ok(best),
+            None => crate::bail_code!(ErrorCode::NoFrameFound, "未找到合适的外部黑框"),
+        }
+    }
+
+    /// 从轮廓中提取四边形
+    pub fn approx_quad(&self, best: &ContourInfo) -> Result<Quad> {
         // 使用轮廓近似算法提取四边形
         let mut approx_curve = Vector::<Point2i>::new();
-        let epsilon = ImageProcessingConfig::EPSILON_FACTOR * imgproc::arc_length(&best.points, true)?; 
+        let epsilon = self.config.epsilon_factor * imgproc::arc_length(&best.points, true)?; 
         imgproc::approx_poly_dp(&best.points, &mut approx_curve, epsilon, true)?;
         
         // 如果点数不是4，使用凸包作为备选方案
@@ -112,37 +153,191 @@ impl LocationModule {
             
             // 如果凸包逼近后仍然不是4个点，则报错
             if approx_curve.len() != 4 {
-                anyhow::bail!("未能找到合适的四边形，原始轮廓顶点数: {}，凸包逼近后顶点数: {}", 
+                crate::bail_code!(ErrorCode::QuadNotFound, "未能找到合适的四边形，原始轮廓顶点数: {}，凸包逼近后顶点数: {}", 
                     best.points.len(), approx_curve.len());
             }
         }
         
         // 提取四个点
-        let mut points_array: [Point2i; 4] = [
-            Point2i::from(approx_curve.get(0)?),
-            Point2i::from(approx_curve.get(1)?),
-            Point2i::from(approx_curve.get(2)?),
-            Point2i::from(approx_curve.get(3)?),
-        ];
+        let mut points_array = [Point2f::default(); 4];
+        for (i, point) in points_array.iter_mut().enumerate() {
+            let p = approx_curve.get(i)?;
+            *point = Point2f::new(p.x as f32, p.y as f32);
+        }
         
         // 确保四个点按顺时针方向排列，从左上角开始
         Self::order_points(&mut points_array);
         
-        Ok(Quad {
-            points: points_array,
-        })
+        Ok(Quad::from_corners(points_array))
+    }
+
+    /// 亚像素精修：对每条边附近的轮廓点做稳健直线拟合，相邻直线求交得到顶点，再用灰度图做角点亚像素优化
+    /// 任一条边的拟合失败时返回原四边形，单个顶点移动过远时保留原顶点
+    pub fn refine_boundary(&self, quad: &Quad, contour: &Vector<Point2i>, gray: &Mat) -> Result<Quad> {
+        // 1. 逐边拟合直线，直线表示为(vx, vy, x0, y0)
+        let mut lines = [(0.0, 0.0, 0.0, 0.0); 4];
+        for (i, line) in lines.iter_mut().enumerate() {
+            let start = quad.corners[i];
+            let end = quad.corners[(i + 1) % 4];
+            let edge_points = Self::edge_points(contour, start, end);
+            if edge_points.len() < BoundaryRefineConfig::MIN_EDGE_POINTS {
+                return Ok(quad.clone());
+            }
+
+            let mut fitted = Mat::default();
+            imgproc::fit_line(&edge_points, &mut fitted, imgproc::DIST_HUBER, 0.0, 0.01, 0.01)?;
+            *line = (
+                *fitted.at::<f32>(0)? as f64,
+                *fitted.at::<f32>(1)? as f64,
+                *fitted.at::<f32>(2)? as f64,
+                *fitted.at::<f32>(3)? as f64,
+            );
+        }
+
+        // 2. 第i个顶点是第i-1条边和第i条边的交点
+        let mut corners = quad.corners;
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let Some((x, y)) = Self::intersect(lines[(i + 3) % 4], lines[i]) else {
+                return Ok(quad.clone());
+            };
+            if distance(x, y, corner.x as f64, corner.y as f64) <= BoundaryRefineConfig::MAX_CORNER_SHIFT {
+                *corner = Point2f::new(x as f32, y as f32);
+            }
+        }
+
+        // 3. 角点亚像素优化
+        let mut refined = Vector::<Point2f>::from_slice(&corners);
+        let window = BoundaryRefineConfig::SUB_PIX_WINDOW;
+        let criteria = TermCriteria::new(
+            opencv::core::TermCriteria_COUNT + opencv::core::TermCriteria_EPS,
+            BoundaryRefineConfig::SUB_PIX_MAX_ITER,
+            BoundaryRefineConfig::SUB_PIX_EPSILON,
+        )?;
+        imgproc::corner_sub_pix(gray, &mut refined, Size::new(window, window), Size::new(-1, -1), criteria)?;
+        for (corner, refined) in corners.iter_mut().zip(refined.iter()) {
+            let shift = distance(refined.x as f64, refined.y as f64, corner.x as f64, corner.y as f64);
+            if shift <= BoundaryRefineConfig::MAX_CORNER_SHIFT {
+                *corner = refined;
+            }
+        }
+
+        Ok(Quad::from_corners(corners))
+    }
+
+    /// 取出靠近一条边的轮廓点，两端各去掉一段以避开圆角
+    fn edge_points(contour: &Vector<Point2i>, start: Point2f, end: Point2f) -> Vector<Point2f> {
+        let (dx, dy) = ((end.x - start.x) as f64, (end.y - start.y) as f64);
+        let length = (dx * dx + dy * dy).sqrt();
+        let mut edge_points = Vector::<Point2f>::new();
+        if length < 1.0 {
+            return edge_points;
+        }
+        let (ux, uy) = (dx / length, dy / length);
+        let trim = length * BoundaryRefineConfig::EDGE_TRIM_RATIO;
+
+        for point in contour.iter() {
+            let (px, py) = (point.x as f64 - start.x as f64, point.y as f64 - start.y as f64);
+            let along = px * ux + py * uy;
+            let across = (px * uy - py * ux).abs();
+            if (trim..=length - trim).contains(&along) && across <= BoundaryRefineConfig::EDGE_BAND {
+                edge_points.push(Point2f::new(point.x as f32, point.y as f32));
+            }
+        }
+        edge_points
+    }
+
+    /// 两条直线的交点，直线为fit_line输出的(vx, vy, x0, y0)，近似平行时返回None
+    fn intersect(a: (f64, f64, f64, f64), b: (f64, f64, f64, f64)) -> Option<(f64, f64)> {
+        let (avx, avy, ax, ay) = a;
+        let (bvx, bvy, bx, by) = b;
+        let denom = avx * bvy - avy * bvx;
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+        let s = ((bx - ax) * bvy - (by - ay) * bvx) / denom;
+        Some((ax + s * avx, ay + s * avy))
+    }
+
+    /// 四角定位块定位：找出实心方块，取离画面四个角最近的方块中心组成四边形
+    pub fn infer_corners(&self, processed_image: &ProcessedImage) -> Result<Quad> {
+        let candidates = self.detect_corner_marks(&processed_image.closed)?;
+
+        let w = processed_image.closed.cols() as f64;
+        let h = processed_image.closed.rows() as f64;
+        let max_distance = CornerLocatorConfig::MAX_CORNER_DISTANCE * (w * w + h * h).sqrt();
+        let corners = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)];
+
+        let mut points = [Point2f::default(); 4];
+        let mut used = Vec::new();
+        for (i, &(corner_x, corner_y)) in corners.iter().enumerate() {
+            let nearest = candidates.iter().enumerate()
+                .filter(|(index, _)| !used.contains(index))
+                .map(|(index, &(x, y))| (index, x, y, distance(x, y, corner_x, corner_y)))
+                .filter(|&(_, _, _, d)| d <= max_distance)
+                .min_by(|a, b| a.3.total_cmp(&b.3));
+            match nearest {
+                Some((index, x, y, _)) => {
+                    used.push(index);
+                    points[i] = Point2f::new(x as f32, y as f32);
+                }
+                None => crate::bail_code!(ErrorCode::CornerMarksNotFound, "未找到第{}个角的定位块，共找到{}个候选", i + 1, candidates.len()),
+            }
+        }
+
+        Self::order_points(&mut points);
+        Ok(Quad::from_corners(points))
+    }
+
+    /// 寻找实心方块，返回中心坐标
+    pub fn detect_corner_marks(&self, morphology: &Mat) -> Result<Vec<(f64, f64)>> {
+        let mut contours = Vector::<Vector<Point2i>>::new();
+        imgproc::find_contours(
+            morphology,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+            Point2i::new(0, 0),
+        )?;
+
+        let image_area = morphology.rows() as f64 * morphology.cols() as f64;
+        let mut centers = Vec::new();
+        for contour in contours.iter() {
+            let area = imgproc::contour_area(&contour, false)?;
+            let area_ratio = area / image_area;
+            if !(CornerLocatorConfig::MIN_AREA_RATIO..=CornerLocatorConfig::MAX_AREA_RATIO).contains(&area_ratio) {
+                continue;
+            }
+
+            let bounding_rect = imgproc::bounding_rect(&contour)?;
+            let aspect = bounding_rect.width as f64 / bounding_rect.height.max(1) as f64;
+            if (aspect - 1.0).abs() > CornerLocatorConfig::MAX_ASPECT_DEVIATION {
+                continue;
+            }
+            let solidity = area / (bounding_rect.width as f64 * bounding_rect.height as f64).max(1.0);
+            if solidity < CornerLocatorConfig::MIN_SOLIDITY {
+                continue;
+            }
+
+            let moments = imgproc::moments(&contour, false)?;
+            if moments.m00 <= 0.0 {
+                continue;
+            }
+            centers.push((moments.m10 / moments.m00, moments.m01 / moments.m00));
+        }
+
+        Ok(centers)
     }
 
     /// 对四边形的四个顶点进行排序，确保按顺时针方向排列，从左上角开始
-    fn order_points(pts: &mut [Point2i; 4]) {
+    fn order_points(pts: &mut [Point2f; 4]) {
         // 计算质心
-        let centroid_x = (pts[0].x + pts[1].x + pts[2].x + pts[3].x) as f32 / 4.0;
-        let centroid_y = (pts[0].y + pts[1].y + pts[2].y + pts[3].y) as f32 / 4.0;
+        let centroid_x = (pts[0].x + pts[1].x + pts[2].x + pts[3].x) / 4.0;
+        let centroid_y = (pts[0].y + pts[1].y + pts[2].y + pts[3].y) / 4.0;
         
         // 按角度排序
         pts.sort_by(|a, b| {
-            let angle_a = (a.y as f32 - centroid_y).atan2(a.x as f32 - centroid_x);
-            let angle_b = (b.y as f32 - centroid_y).atan2(b.x as f32 - centroid_x);
+            let angle_a = (a.y - centroid_y).atan2(a.x - centroid_x);
+            let angle_b = (b.y - centroid_y).atan2(b.x - centroid_x);
             
             angle_a.partial_cmp(&angle_b).unwrap()
         });
@@ -163,8 +358,91 @@ impl LocationModule {
         pts.rotate_left(min_index);
     }
 
-    pub fn validate_boundary(&self, _boundary: &Quad) -> bool {
-        // 验证边界
-        true
+    /// 几何校验：依次检查到画面边缘的距离、面积、凸性和内角，失败时返回对应的错误码和拍摄建议
+    pub fn validate_boundary(&self, boundary: &Quad, image: &Mat) -> Result<()> {
+        let w = image.cols() as f64;
+        let h = image.rows() as f64;
+
+        // 1. 顶点贴近画面边缘说明答题卡可能没有拍全
+        let margin = boundary.float_points().iter()
+            .map(|&(x, y)| x.min(y).min(w - x).min(h - y))
+            .fold(f64::INFINITY, f64::min);
+        if margin < BoundaryValidationConfig::MIN_BORDER_MARGIN * w {
+            crate::bail_code!(ErrorCode::BoundaryTouchesEdge, "外框距画面边缘只有{:.1}%，答题卡可能未拍全，请拉远一些", margin / w * 100.0);
+        }
+
+        // 2. 面积过小
+        let area_ratio = boundary.area() / (w * h);
+        if area_ratio < self.config.min_area_ratio {
+            crate::bail_code!(ErrorCode::BoundaryTooSmall, "外框面积只占画面的{:.0}%，请靠近一些", area_ratio * 100.0);
+        }
+
+        // 3. 凸性
+        let corners = Vector::<Point2f>::from_slice(&boundary.corners);
+        if !imgproc::is_contour_convex(&corners)? {
+            crate::bail_code!(ErrorCode::BoundaryDistorted, "外框不是凸四边形，请重新拍摄");
+        }
+
+        // 4. 内角
+        let points = boundary.float_points();
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let (prev_x, prev_y) = points[(i + 3) % 4];
+            let (next_x, next_y) = points[(i + 1) % 4];
+            let (ax, ay, bx, by) = (prev_x - x, prev_y - y, next_x - x, next_y - y);
+            let angle = (ax * by - ay * bx).abs().atan2(ax * bx + ay * by).to_degrees();
+            if !(BoundaryValidationConfig::MIN_INTERIOR_ANGLE..=BoundaryValidationConfig::MAX_INTERIOR_ANGLE).contains(&angle) {
+                crate::bail_code!(ErrorCode::BoundaryDistorted, "外框第{}个顶点的内角为{:.0}度，拍摄角度过斜，请正对答题卡", i + 1, angle);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 宽高比校验，答题卡可能旋转了90度，按横竖两种方向取吻合度较高的一个
+    pub fn validate_aspect(boundary: &Quad, expected_aspect: f64) -> Result<()> {
+        let (quad_w, quad_h) = boundary.size();
+        let ratio = quad_w / quad_h.max(1.0);
+        let score = |expected: f64| ratio.min(expected) / ratio.max(expected);
+        let aspect_score = score(expected_aspect).max(score(1.0 / expected_aspect));
+        if aspect_score < BoundaryValidationConfig::MIN_ASPECT_SCORE {
+            crate::bail_code!(ErrorCode::BoundaryAspectMismatch, "外框宽高比{:.2}与模板{:.2}不符，可能不是该模板的答题卡或被遮挡", ratio, expected_aspect);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::error_code;
+
+    #[test]
+    fn test_line_intersect() {
+        let horizontal = (1.0, 0.0, 0.0, 0.0);
+        assert_eq!(LocationModule::intersect(horizontal, (0.0, 1.0, 5.0, 3.0)), Some((5.0, 0.0)));
+        assert_eq!(LocationModule::intersect((1.0, 1.0, 0.0, 0.0), (1.0, -1.0, 0.0, 10.0)), Some((5.0, 5.0)));
+        // 平行线没有交点
+        assert_eq!(LocationModule::intersect(horizontal, (-1.0, 0.0, 0.0, 7.0)), None);
+    }
+
+    /// 由四个顶点构造四边形
+    fn test_quad(points: [(f32, f32); 4]) -> Quad {
+        Quad::from_corners(points.map(|(x, y)| Point2f::new(x, y)))
+    }
+
+    #[test]
+    fn test_validate_boundary() -> Result<()> {
+        let location_module = LocationModule::new(ImageProcessingConfig::default());
+        let image = Mat::new_rows_cols_with_default(800, 600, opencv::core::CV_8UC1, opencv::core::Scalar::all(0.0))?;
+        let code = |quad: &Quad| location_module.validate(quad, &image, 0.75).err().map(|e| error_code(&e));
+
+        assert_eq!(code(&test_quad([(50.0, 50.0), (550.0, 50.0), (550.0, 717.0), (50.0, 717.0)])), None);
+        // 横向拍摄的答题卡
+        assert_eq!(code(&test_quad([(50.0, 150.0), (550.0, 150.0), (550.0, 525.0), (50.0, 525.0)])), None);
+        assert_eq!(code(&test_quad([(2.0, 50.0), (550.0, 50.0), (550.0, 717.0), (2.0, 717.0)])), Some(ErrorCode::BoundaryTouchesEdge));
+        assert_eq!(code(&test_quad([(200.0, 200.0), (300.0, 200.0), (300.0, 333.0), (200.0, 333.0)])), Some(ErrorCode::BoundaryTooSmall));
+        assert_eq!(code(&test_quad([(50.0, 50.0), (550.0, 50.0), (300.0, 400.0), (50.0, 750.0)])), Some(ErrorCode::BoundaryDistorted));
+        assert_eq!(code(&test_quad([(50.0, 250.0), (550.0, 250.0), (550.0, 500.0), (50.0, 500.0)])), Some(ErrorCode::BoundaryAspectMismatch));
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,9 @@
 pub mod location;
 pub mod engine;
 pub mod fill;
-pub mod assist_location;
\ No newline at end of file
+pub mod assist_location;
+pub mod code;
+pub mod registry;
+pub mod session;
+pub mod score;
+pub mod generate;
\ No newline at end of file
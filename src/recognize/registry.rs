@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+use anyhow::Result;
+use opencv::core::Mat;
+use crate::config::{ConfigOverride, TemplateConfig};
+use crate::error::{error_code, ErrorCode, WithCode};
+use crate::models::{AlignedSheet, InferenceOptions, MobileOutput, TemplateEntry};
+use crate::myutils::myjson::from_json;
+use crate::recognize::engine::RecEngine;
+
+/// 模板注册表：持有多个模板的引擎，识别时自动选择匹配的模板
+pub struct TemplateRegistry {
+    templates: Vec<(String, RecEngine)>,
+}
+
+impl TemplateRegistry {
+    /// 从TemplateEntry数组的JSON创建注册表，config对所有模板生效，参数优先级同RecEngine::with_config
+    pub fn new(templates_input: &String, config: Option<ConfigOverride>) -> Result<Self> {
+        let entries: Vec<TemplateEntry> = from_json(templates_input).with_code(ErrorCode::MarkJsonInvalid, "模板列表解析失败")?;
+        Self::from_entries(entries, config)
+    }
+
+    /// 使用已解析的模板列表创建注册表
+    pub fn from_entries(entries: Vec<TemplateEntry>, config: Option<ConfigOverride>) -> Result<Self> {
+        if entries.is_empty() {
+            crate::bail_code!(ErrorCode::MarkJsonInvalid, "模板列表为空");
+        }
+
+        let mut names = HashSet::new();
+        let mut templates = Vec::with_capacity(entries.len());
+        for entry in entries {
+            if !names.insert(entry.name.clone()) {
+                crate::bail_code!(ErrorCode::MarkJsonInvalid, "模板名称重复: {}", entry.name);
+            }
+            let engine = RecEngine::from_mark(entry.mark, config.clone())?;
+            templates.push((entry.name, engine));
+        }
+
+        Ok(Self { templates })
+    }
+
+    /// 已注册的模板名称
+    pub fn names(&self) -> Vec<&str> {
+        self.templates.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn inference(&self, image: &Mat) -> Result<MobileOutput> {
+        self.inference_with_options(image, &InferenceOptions::default())
+    }
+
+    /// 用分数最高的模板识别，结果中返回模板名称和匹配分数
+    pub fn inference_with_options(&self, image: &Mat, options: &InferenceOptions) -> Result<MobileOutput> {
+        let (index, score, aligned) = self.identify(image)?;
+        let (name, engine) = &self.templates[index];
+        let mut mobile_output = engine.inference_aligned(image, &aligned, options)?;
+        mobile_output.template = Some(name.clone());
+        mobile_output.match_score = Some(score);
+
+        Ok(mobile_output)
+    }
+
+    /// 逐个模板尝试对齐并评分，返回最佳模板的序号、匹配分数和对齐结果
+    pub fn identify(&self, image: &Mat) -> Result<(usize, f64, AlignedSheet)> {
+        // 1. 逐个模板匹配
+        let mut best = None;
+        let mut second_score = f64::NEG_INFINITY;
+        let mut reasons = Vec::new();
+        let mut codes = Vec::new();
+        for (index, (name, engine)) in self.templates.iter().enumerate() {
+            match engine.match_template(image) {
+                Ok((score, aligned)) => {
+                    reasons.push(format!("{}: 匹配分数{:.2}", name, score));
+                    match &best {
+                        Some((_, best_score, _)) if score <= *best_score => second_score = second_score.max(score),
+                        Some((_, best_score, _)) => {
+                            second_score = *best_score;
+                            best = Some((index, score, aligned));
+                        }
+                        None => best = Some((index, score, aligned)),
+                    }
+                }
+                Err(e) => {
+                    reasons.push(format!("{}: {}", name, e));
+                    codes.push(error_code(&e));
+                }
+            }
+        }
+
+        // 2. 所有模板都无法对齐时，如果原因一致则直接报告该错误，如未找到外框
+        let Some((index, score, aligned)) = best else {
+            let code = match codes.first() {
+                Some(&code) if codes.iter().all(|&c| c == code) => code,
+                _ => ErrorCode::TemplateNotMatched,
+            };
+            crate::bail_code!(code, "图片无法与任何模板对齐: {}", reasons.join("; "));
+        };
+        if score < TemplateConfig::MIN_MATCH_SCORE {
+            crate::bail_code!(ErrorCode::TemplateNotMatched, "图片与所有模板都不匹配: {}", reasons.join("; "));
+        }
+        // 多个模板分数接近时无法确定，宁可报错也不用错模板识别
+        if score - second_score < TemplateConfig::MIN_SCORE_GAP {
+            crate::bail_code!(ErrorCode::TemplateNotMatched, "多个模板的匹配分数接近，无法确定模板: {}", reasons.join("; "));
+        }
+
+        Ok((index, score, aligned))
+    }
+
+    /// 按序号获取模板的引擎
+    pub fn engine(&self, index: usize) -> Option<&RecEngine> {
+        self.templates.get(index).map(|(_, engine)| engine)
+    }
+
+    /// 已注册的模板数量
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::Result;
+use crate::config::KeyCaptureConfig;
+use crate::models::{AmbiguousQuestion, AnswerKey, KeyQuestion, MobileOutput, PartialCreditRule, QuestionScore, RecResult, RecStatus, RecType, ScoreOutcome};
+
+#[derive(Default)]
+pub struct ScoreModule;
+
+impl ScoreModule {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// 按标准答案为每道题判分并计算总分，标准答案需已通过AnswerKey::validate
+    pub fn infer(&self, mobile_output: &mut MobileOutput, answer_key: &AnswerKey) -> Result<()> {
+        let scores = mobile_output.rec_results.iter().zip(answer_key.questions.iter())
+            .map(|(rec_result, question)| {
+                question.as_ref().map(|question| self.score_question(rec_result, question, answer_key.multiple_rule))
+            })
+            .collect::<Vec<Option<QuestionScore>>>();
+
+        mobile_output.total_score = Some(scores.iter().flatten().map(|score| score.score).sum());
+        mobile_output.max_score = Some(scores.iter().flatten().map(|score| score.points).sum());
+        mobile_output.scores = scores;
+
+        Ok(())
+    }
+
+    /// 从老师填涂的答案卡识别结果生成标准答案，同时返回所有不确定的题目
+    /// 模板中已有标准答案时沿用其分值和评分规则，标记为不计分的题目跳过
+    pub fn capture_key(&self, mobile_output: &MobileOutput, template_key: Option<&AnswerKey>) -> (AnswerKey, Vec<AmbiguousQuestion>) {
+        let mut questions = Vec::with_capacity(mobile_output.rec_results.len());
+        let mut ambiguous = Vec::new();
+        for (index, rec_result) in mobile_output.rec_results.iter().enumerate() {
+            let template_question = match template_key {
+                Some(template_key) => match &template_key.questions[index] {
+                    Some(question) => Some(question),
+                    None => {
+                        questions.push(None);
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            if let Some(reason) = Self::ambiguous_reason(rec_result) {
+                ambiguous.push(AmbiguousQuestion {
+                    index,
+                    confidence: rec_result.confidence,
+                    reason,
+                });
+            }
+
+            let answer = rec_result.rec_result.iter().enumerate()
+                .filter(|(_, &selected)| selected)
+                .map(|(index, _)| index)
+                .collect::<Vec<usize>>();
+            questions.push(Some(KeyQuestion {
+                answer: if rec_result.rec_tpye == RecType::NumericGrid { vec![] } else { answer },
+                text: rec_result.text.clone(),
+                points: template_question.map_or(KeyCaptureConfig::DEFAULT_POINTS, |question| question.points),
+                partial_points: template_question.and_then(|question| question.partial_points),
+                rule: template_question.and_then(|question| question.rule),
+            }));
+        }
+
+        let answer_key = AnswerKey {
+            questions,
+            multiple_rule: template_key.map(|template_key| template_key.multiple_rule).unwrap_or_default(),
+        };
+        (answer_key, ambiguous)
+    }
+
+    /// 标准答案要求每题都明确作答，返回不满足时的原因
+    fn ambiguous_reason(rec_result: &RecResult) -> Option<String> {
+        if rec_result.rec_tpye == RecType::NumericGrid {
+            let unclear = rec_result.positions.iter().enumerate()
+                .filter(|(_, position)| position.status != RecStatus::Answered)
+                .map(|(index, _)| (index + 1).to_string())
+                .collect::<Vec<String>>();
+            if !unclear.is_empty() {
+                return Some(format!("第{}位未作答或多涂", unclear.join("、")));
+            }
+        }
+        match rec_result.status {
+            RecStatus::Blank => return Some("未作答".to_string()),
+            RecStatus::MultipleAnswers => return Some("多个选项填涂程度相当".to_string()),
+            RecStatus::Answered => {}
+        }
+        if rec_result.borderline {
+            return Some("存在填涂率接近阈值的选项".to_string());
+        }
+        if rec_result.confidence < KeyCaptureConfig::MIN_CONFIDENCE {
+            return Some(format!("置信度{:.2}过低", rec_result.confidence));
+        }
+        None
+    }
+
+    /// 单题判分
+    pub fn score_question(&self, rec_result: &RecResult, question: &KeyQuestion, default_rule: PartialCreditRule) -> QuestionScore {
+        let outcome = match rec_result.rec_tpye {
+            RecType::NumericGrid => match &rec_result.text {
+                Some(text) if Some(text) == question.text.as_ref() => ScoreOutcome::Correct,
+                _ if rec_result.status == RecStatus::Blank => ScoreOutcome::Blank,
+                _ => ScoreOutcome::Wrong,
+            },
+            _ => {
+                let selected = rec_result.rec_result.iter().enumerate()
+                    .filter(|(_, &selected)| selected)
+                    .map(|(index, _)| index)
+                    .collect::<Vec<usize>>();
+                let rule = question.rule.unwrap_or(default_rule);
+                Self::choice_outcome(&selected, &question.answer, rec_result.rec_tpye, rule)
+            }
+        };
+
+        let score = match outcome {
+            ScoreOutcome::Correct => question.points,
+            ScoreOutcome::Partial => question.partial_points.unwrap_or(question.points / 2.0),
+            ScoreOutcome::Wrong | ScoreOutcome::Blank => 0.0,
+        };
+
+        QuestionScore {
+            score,
+            points: question.points,
+            outcome,
+        }
+    }
+
+    /// 选择题判分：全对、少选（仅多选题且规则允许）、有错选或未作答
+    fn choice_outcome(selected: &[usize], answer: &[usize], rec_type: RecType, rule: PartialCreditRule) -> ScoreOutcome {
+        if selected.is_empty() {
+            return ScoreOutcome::Blank;
+        }
+        if selected.iter().any(|index| !answer.contains(index)) {
+            return ScoreOutcome::Wrong;
+        }
+        if selected.len() == answer.len() {
+            return ScoreOutcome::Correct;
+        }
+        match (rec_type, rule) {
+            (RecType::MultipleChoice, PartialCreditRule::PartialSubset) => ScoreOutcome::Partial,
+            _ => ScoreOutcome::Wrong,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GridSpec, Mark};
+    use crate::myutils::myjson::from_json;
+    use crate::recognize::fill::tests::{decide_fixed, grid_rates};
+
+    #[test]
+    fn test_score() -> Result<()> {
+        let mut mark = Mark::for_test(&[(1, 4), (2, 4), (2, 4), (3, 10), (1, 4), (1, 4)]);
+        mark.rec_items[3].grid = Some(GridSpec { columns: 1, blank_option: false });
+        let answer_key: AnswerKey = from_json(r#"{"questions": [
+            {"answer": [1], "points": 2},
+            {"answer": [0, 2], "points": 3},
+            {"answer": [0, 2], "points": 3},
+            {"text": "5", "points": 4},
+            {"answer": [0], "points": 1},
+            null
+        ]}"#)?;
+        answer_key.validate(&mark)?;
+
+        let mut output = MobileOutput::new(&mark);
+        decide_fixed(&mut output, &[
+            &[0.05, 0.9, 0.05, 0.05],
+            &[0.9, 0.05, 0.05, 0.05],
+            &[0.9, 0.9, 0.05, 0.05],
+            grid_rates(10, &[5]).as_slice(),
+            &[0.05, 0.05, 0.05, 0.05],
+            &[0.9, 0.05, 0.05, 0.05],
+        ])?;
+        let score_module = ScoreModule::new();
+        score_module.infer(&mut output, &answer_key)?;
+
+        // 全对、少选得一半、错选、数字串相同、未作答、不计分
+        let outcomes = output.scores.iter().map(|score| score.as_ref().map(|score| (score.outcome, score.score))).collect::<Vec<_>>();
+        assert_eq!(outcomes, vec![
+            Some((ScoreOutcome::Correct, 2.0)),
+            Some((ScoreOutcome::Partial, 1.5)),
+            Some((ScoreOutcome::Wrong, 0.0)),
+            Some((ScoreOutcome::Correct, 4.0)),
+            Some((ScoreOutcome::Blank, 0.0)),
+            None,
+        ]);
+        assert_eq!(output.total_score, Some(7.5));
+        assert_eq!(output.max_score, Some(13.0));
+
+        // 题目自带的规则和少选分值优先
+        let question: KeyQuestion = from_json(r#"{"answer": [0, 2], "points": 3, "rule": 1}"#)?;
+        let score = score_module.score_question(&output.rec_results[1], &question, answer_key.multiple_rule);
+        assert_eq!((score.outcome, score.score), (ScoreOutcome::Wrong, 0.0));
+        let question: KeyQuestion = from_json(r#"{"answer": [0, 2], "points": 3, "partial_points": 1}"#)?;
+        let score = score_module.score_question(&output.rec_results[1], &question, answer_key.multiple_rule);
+        assert_eq!((score.outcome, score.score), (ScoreOutcome::Partial, 1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_capture_key() -> Result<()> {
+        let mut mark = Mark::for_test(&[(1, 4), (1, 4), (1, 4), (3, 20), (2, 4)]);
+        mark.rec_items[3].grid = Some(GridSpec { columns: 2, blank_option: false });
+        let mut output = MobileOutput::new(&mark);
+        decide_fixed(&mut output, &[
+            &[0.9, 0.05, 0.05, 0.05],
+            &[0.05, 0.05, 0.05, 0.05],
+            &[0.9, 0.9, 0.05, 0.05],
+            grid_rates(20, &[2]).as_slice(),
+            &[0.9, 0.05, 0.9, 0.47],
+        ])?;
+        let score_module = ScoreModule::new();
+
+        // 未作答、多涂、网格缺位、临界填涂都需要老师确认
+        let (answer_key, ambiguous) = score_module.capture_key(&output, None);
+        let reasons = ambiguous.iter().map(|question| (question.index, question.reason.as_str())).collect::<Vec<_>>();
+        assert_eq!(reasons, vec![
+            (1, "未作答"),
+            (2, "多个选项填涂程度相当"),
+            (3, "第2位未作答或多涂"),
+            (4, "存在填涂率接近阈值的选项"),
+        ]);
+        let first = answer_key.questions[0].as_ref().unwrap();
+        assert_eq!((first.answer.clone(), first.points), (vec![0], KeyCaptureConfig::DEFAULT_POINTS));
+        let grid = answer_key.questions[3].as_ref().unwrap();
+        assert!(grid.answer.is_empty());
+        assert_eq!(grid.text.as_deref(), Some("2?"));
+
+        // 沿用模板中的分值和规则，不计分的题目跳过
+        let template_key: AnswerKey = from_json(r#"{"questions": [
+            {"answer": [3], "points": 5, "partial_points": 2},
+            null,
+            {"answer": [0], "points": 1},
+            {"text": "00", "points": 1},
+            {"answer": [0], "points": 1}
+        ], "multiple_rule": 1}"#)?;
+        let (answer_key, ambiguous) = score_module.capture_key(&output, Some(&template_key));
+        assert_eq!(ambiguous.iter().map(|question| question.index).collect::<Vec<usize>>(), vec![2, 3, 4]);
+        let first = answer_key.questions[0].as_ref().unwrap();
+        assert_eq!((first.answer.clone(), first.points, first.partial_points), (vec![0], 5.0, Some(2.0)));
+        assert!(answer_key.questions[1].is_none());
+        assert_eq!(answer_key.multiple_rule, PartialCreditRule::AllOrNothing);
+        Ok(())
+    }
+}
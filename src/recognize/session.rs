@@ -0,0 +1,175 @@
+use std::sync::Mutex;
+use anyhow::Result;
+use opencv::core::Mat;
+use crate::config::ConfigOverride;
+use crate::error::{ErrorCode, WithCode};
+use crate::models::{InferenceOptions, MobileOutput, MultiPageMark, ScoreOutcome, TemplateEntry};
+use crate::myutils::myjson::from_json;
+use crate::recognize::registry::TemplateRegistry;
+
+/// 多页识别会话：逐页扫描同一考生的答题卡，合并为一份结果
+/// 每张图片自动识别页码，重复扫描同一页时以最后一次为准
+pub struct ScanSession {
+    /// 每页一个模板
+    registry: TemplateRegistry,
+    /// 每页的识别项目数，用于计算跨页题号
+    page_sizes: Vec<usize>,
+    /// 已扫描页面的识别结果
+    pages: Mutex<Vec<Option<MobileOutput>>>,
+}
+
+impl ScanSession {
+    /// 从MultiPageMark的JSON创建会话，config对所有页面生效
+    pub fn new(mark_input: &String, config: Option<ConfigOverride>) -> Result<Self> {
+        let multi_page: MultiPageMark = from_json(mark_input).with_code(ErrorCode::MarkJsonInvalid, "多页模板解析失败")?;
+        let page_sizes = multi_page.pages.iter().map(|page| page.rec_items.len()).collect::<Vec<usize>>();
+        let entries = multi_page.pages.into_iter().enumerate()
+            .map(|(index, mark)| TemplateEntry { name: format!("第{}页", index + 1), mark })
+            .collect();
+        let registry = TemplateRegistry::from_entries(entries, config)?;
+
+        Ok(Self {
+            registry,
+            pages: Mutex::new(vec![None; page_sizes.len()]),
+            page_sizes,
+        })
+    }
+
+    /// 页数
+    pub fn page_count(&self) -> usize {
+        self.page_sizes.len()
+    }
+
+    /// 识别一页并记入会话，返回该页的识别结果，题号按跨页连续编排
+    pub fn add_page(&self, image: &Mat, options: &InferenceOptions) -> Result<MobileOutput> {
+        // 1. 识别页码
+        let (index, score, aligned) = self.registry.identify(image)?;
+        let Some(engine) = self.registry.engine(index) else {
+            crate::bail_code!(ErrorCode::Unknown, "页码{}超出范围", index + 1);
+        };
+
+        // 2. 识别并编排题号
+        let mut mobile_output = engine.inference_aligned(image, &aligned, options)?;
+        mobile_output.page = Some(index + 1);
+        mobile_output.match_score = Some(score);
+        let offset = self.page_sizes[..index].iter().sum::<usize>();
+        for (i, rec_result) in mobile_output.rec_results.iter_mut().enumerate() {
+            rec_result.question_no = Some(offset + i + 1);
+        }
+
+        // 3. 记入会话
+        self.record_page(index, &mobile_output);
+
+        Ok(mobile_output)
+    }
+
+    /// 记录第index页（从0开始）的识别结果，调试图片只随单页结果返回
+    fn record_page(&self, index: usize, mobile_output: &MobileOutput) {
+        let mut stored = mobile_output.clone();
+        stored.debug_images.clear();
+        stored.debug_error = None;
+        if let Some(page) = self.pages.lock().unwrap().get_mut(index) {
+            *page = Some(stored);
+        }
+    }
+
+    /// 按页码顺序合并已扫描的页面，有页面未扫描时返回PagesIncomplete和已扫描部分的结果
+    /// 任意一页填涂了缺考标记时，所有页面的作答结果都被清空
+    pub fn merge(&self) -> MobileOutput {
+        let pages = self.pages.lock().unwrap();
+        let missing_pages = pages.iter().enumerate()
+            .filter(|(_, page)| page.is_none())
+            .map(|(index, _)| index + 1)
+            .collect::<Vec<usize>>();
+
+        let mut merged = MobileOutput::empty();
+        if !missing_pages.is_empty() {
+            merged.code = ErrorCode::PagesIncomplete.into();
+            merged.message = format!("还有页面未扫描: {:?}", missing_pages);
+        }
+        for page in pages.iter().flatten() {
+            merged.rec_results.extend(page.rec_results.iter().cloned());
+            merged.codes.extend(page.codes.iter().cloned());
+            merged.crops.extend(page.crops.iter().cloned());
+            merged.flags.extend(page.flags.iter().cloned());
+            // 没有标准答案的页面补null，保持与rec_results一一对应
+            if page.scores.is_empty() {
+                merged.scores.extend(vec![None; page.rec_results.len()]);
+            } else {
+                merged.scores.extend(page.scores.iter().cloned());
+            }
+            if let (Some(total_score), Some(max_score)) = (page.total_score, page.max_score) {
+                merged.total_score = Some(merged.total_score.unwrap_or(0.0) + total_score);
+                merged.max_score = Some(merged.max_score.unwrap_or(0.0) + max_score);
+            }
+            merged.absent |= page.absent;
+            merged.disciplinary |= page.disciplinary;
+        }
+        if merged.scores.iter().all(|score| score.is_none()) {
+            merged.scores.clear();
+        }
+        if merged.absent {
+            merged.suppress_answers();
+            for score in merged.scores.iter_mut().flatten() {
+                score.score = 0.0;
+                score.outcome = ScoreOutcome::Blank;
+            }
+            merged.total_score = merged.total_score.map(|_| 0.0);
+        }
+        merged.missing_pages = missing_pages;
+
+        merged
+    }
+
+    /// 清空已扫描的页面，开始下一位考生
+    pub fn reset(&self) {
+        self.pages.lock().unwrap().iter_mut().for_each(|page| *page = None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Mark, QuestionScore};
+    use crate::myutils::myjson::to_json;
+
+    #[test]
+    fn test_session_merge() -> Result<()> {
+        let pages = [Mark::for_test(&[(1, 4), (1, 4)]), Mark::for_test(&[(1, 4)])];
+        let session = ScanSession::new(&to_json(&MultiPageMark { pages: pages.to_vec() })?, None)?;
+
+        let mut first = MobileOutput::new(&pages[0]);
+        first.rec_results[0].rec_result[1] = true;
+        first.scores = vec![
+            Some(QuestionScore { score: 2.0, points: 2.0, outcome: ScoreOutcome::Correct }),
+            None,
+        ];
+        first.total_score = Some(2.0);
+        first.max_score = Some(2.0);
+        session.record_page(0, &first);
+
+        let merged = session.merge();
+        assert_eq!(merged.code, i32::from(ErrorCode::PagesIncomplete));
+        assert_eq!(merged.missing_pages, vec![2]);
+
+        // 第二页没有标准答案，得分补null保持与题目对应
+        let mut second = MobileOutput::new(&pages[1]);
+        second.absent = true;
+        session.record_page(1, &second);
+        let merged = session.merge();
+        assert_eq!(merged.code, i32::from(ErrorCode::Success));
+        assert_eq!(merged.rec_results.len(), 3);
+        assert_eq!(merged.scores.len(), 3);
+        assert!(merged.scores[2].is_none());
+
+        // 任一页缺考时清空所有作答和得分
+        assert!(merged.absent);
+        assert!(merged.rec_results.iter().all(|rec_result| rec_result.rec_result.iter().all(|&selected| !selected)));
+        assert_eq!(merged.total_score, Some(0.0));
+        assert_eq!(merged.max_score, Some(2.0));
+
+        session.reset();
+        assert_eq!(session.merge().missing_pages, vec![1, 2]);
+        Ok(())
+    }
+}